@@ -1,4 +1,8 @@
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
+use project_dirs::Directory;
+use project_dirs::dir_utils::ProvisionPolicy;
+use project_dirs::doctor::{DirectoryDiagnosis, DoctorReport, Severity};
+use project_dirs::strategy::systemd::{OwnershipPolicy, sysusers_entries};
 use std::path::PathBuf;
 
 const BUILDER_SCHEMA: &str = include_str!("../../builder.schema.json");
@@ -31,6 +35,313 @@ pub struct Cli {
     /// File to read to get project dirs
     #[arg(group = "manifest")]
     pub manifest_file: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Move directories between layouts of two spec entries (e.g. from `~/.app` to XDG). Prints
+    /// the plan (dry-run) or the report. Exits with 1 on conflicts or failures
+    Relocate {
+        /// Spec entry with the old layout
+        #[arg(long)]
+        from: String,
+
+        /// Spec entry with the new layout
+        #[arg(long)]
+        to: String,
+
+        /// Only print the plan
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Leave symlinks in the old locations
+        #[arg(long)]
+        symlinks: bool,
+    },
+
+    /// Print systemd `[Service]` directives (`StateDirectory=` etc. and `ReadWritePaths=`) for
+    /// the FHS system scope of the project
+    SystemdUnit {
+        /// Use directories of the spec entry instead of FHS
+        #[arg(long)]
+        entry: Option<String>,
+
+        /// Mode of the directory, e.g. `runtime=0750`. May be repeated
+        #[arg(long = "mode", value_parser = parse_mode)]
+        modes: Vec<(Directory, u32)>,
+
+        /// Print as json
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Print `tmpfiles.d` lines creating the writable directories of the FHS system scope
+    Tmpfiles {
+        /// Use directories of the spec entry instead of FHS
+        #[arg(long)]
+        entry: Option<String>,
+
+        #[command(flatten)]
+        ownership: OwnershipArgs,
+    },
+
+    /// Print `sysusers.d` lines creating the service user
+    Sysusers {
+        #[command(flatten)]
+        ownership: OwnershipArgs,
+    },
+
+    /// Check directories of all spec entries: existence, type, owner, mode, symlinks,
+    /// filesystem and free space. Exits with 1 on problems
+    Doctor {
+        /// Check only the given spec entry
+        #[arg(long)]
+        entry: Option<String>,
+
+        /// Exit with 1 on warnings as well (e.g. missing or world-writable directories)
+        #[arg(long)]
+        strict: bool,
+
+        /// Print as json
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Overrides of the `ownership` from the manifest
+#[derive(Args)]
+pub struct OwnershipArgs {
+    /// Service user. Required when manifest has no `ownership`
+    #[arg(long)]
+    user: Option<String>,
+
+    /// Group of the directories. Defaults to the user
+    #[arg(long)]
+    group: Option<String>,
+
+    /// Age of the cache files to clean up, e.g. `30d`
+    #[arg(long)]
+    cache_age: Option<String>,
+
+    /// Age of the runtime files to clean up, e.g. `10d`
+    #[arg(long)]
+    runtime_age: Option<String>,
+
+    /// Mode of the directory, e.g. `runtime=0750`. May be repeated
+    #[arg(long = "mode", value_parser = parse_mode)]
+    modes: Vec<(Directory, u32)>,
+}
+
+impl OwnershipArgs {
+    fn policy(self, manifest: Option<&OwnershipPolicy>) -> Option<OwnershipPolicy> {
+        let mut policy = match (self.user, manifest) {
+            (Some(user), Some(manifest)) => OwnershipPolicy {
+                user,
+                ..manifest.clone()
+            },
+            (Some(user), None) => OwnershipPolicy::new(&user),
+            (None, Some(manifest)) => manifest.clone(),
+            (None, None) => return None,
+        };
+
+        policy.group = self.group.or(policy.group);
+        policy.cache_age = self.cache_age.or(policy.cache_age);
+        policy.runtime_age = self.runtime_age.or(policy.runtime_age);
+        policy.modes = self
+            .modes
+            .into_iter()
+            .fold(policy.modes, |modes, (dir, mode)| {
+                modes.with_mode(dir, mode)
+            });
+        Some(policy)
+    }
+}
+
+/// Parse `<directory>=<octal mode>`
+fn parse_mode(value: &str) -> Result<(Directory, u32), String> {
+    let (dir, mode) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected <directory>=<mode>, got {value:?}"))?;
+    let dir: Directory = serde_json::from_value(serde_json::Value::String(dir.to_string()))
+        .map_err(|e| e.to_string())?;
+    let mode = u32::from_str_radix(mode, 8).map_err(|e| format!("invalid mode {mode:?}: {e}"))?;
+    Ok((dir, mode))
+}
+
+fn print_json(value: &impl serde::Serialize) {
+    match serde_json::to_string_pretty(value) {
+        Ok(r) => println!("{r}"),
+        Err(e) => {
+            eprintln!("UNEXPECTED ERROR: Failed to serialize result: {e:?}");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn relocate(
+    builder: &project_dirs_builder::Builder,
+    from: &str,
+    to: &str,
+    dry_run: bool,
+    symlinks: bool,
+) {
+    let plan = match builder.relocation_plan(from, to) {
+        Ok(plan) => plan,
+        Err(error) => {
+            eprintln!("\x1b[93mERROR: Failed to plan relocation\x1b[0m");
+            eprintln!("   {}", error);
+            std::process::exit(1);
+        }
+    };
+
+    if dry_run {
+        print_json(&plan);
+        if plan.conflicts().next().is_some() {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let report = plan.execute(symlinks);
+    print_json(&report);
+    if report.has_problems() {
+        std::process::exit(1);
+    }
+}
+
+fn systemd_unit(
+    builder: &project_dirs_builder::Builder,
+    entry: Option<&str>,
+    modes: Vec<(Directory, u32)>,
+    json: bool,
+) {
+    let policy = modes
+        .into_iter()
+        .fold(ProvisionPolicy::default(), |policy, (dir, mode)| {
+            policy.with_mode(dir, mode)
+        });
+
+    match builder.service_directives(entry, &policy) {
+        Ok(directives) if json => print_json(&directives),
+        Ok(directives) => print!("{directives}"),
+        Err(error) => {
+            eprintln!("\x1b[93mERROR: Failed to generate systemd directives\x1b[0m");
+            eprintln!("   {}", error);
+            std::process::exit(1);
+        }
+    }
+}
+
+fn ownership_policy(
+    builder: &project_dirs_builder::Builder,
+    ownership: OwnershipArgs,
+) -> OwnershipPolicy {
+    match ownership.policy(builder.ownership.as_ref()) {
+        Some(policy) => policy,
+        None => {
+            eprintln!("\x1b[93mERROR: Missing service user\x1b[0m");
+            eprintln!("   Use --user or set `ownership` in the manifest");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn tmpfiles(builder: &project_dirs_builder::Builder, entry: Option<&str>, policy: OwnershipPolicy) {
+    match builder.tmpfiles(entry, &policy) {
+        Ok(entries) => entries.iter().for_each(|e| println!("{e}")),
+        Err(error) => {
+            eprintln!("\x1b[93mERROR: Failed to generate tmpfiles.d entries\x1b[0m");
+            eprintln!("   {}", error);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Human-readable size, e.g. `1.5 GiB`
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{bytes} B"),
+        _ => format!("{size:.1} {}", UNITS[unit]),
+    }
+}
+
+fn print_diagnosis(name: &str, dir: &Directory, diagnosis: &DirectoryDiagnosis) {
+    let dir = serde_json::to_value(dir)
+        .ok()
+        .and_then(|v| v.as_str().map(str::to_string))
+        .unwrap_or_default();
+    println!("{name} {dir}: {}", diagnosis.path.display());
+
+    let mut details = Vec::new();
+    if let Some(kind) = diagnosis.kind {
+        details.push(format!("{kind:?}").to_lowercase());
+    }
+    if let Some(target) = &diagnosis.symlink_target {
+        details.push(format!("symlink to {}", target.display()));
+    }
+    if let Some(owner) = diagnosis.owner {
+        details.push(format!("owner {owner}"));
+    }
+    if let Some(mode) = diagnosis.mode {
+        details.push(format!("mode {mode:04o}"));
+    }
+    if let Some(filesystem) = &diagnosis.filesystem {
+        details.push(filesystem.clone());
+    }
+    if let Some(free_space) = diagnosis.free_space {
+        details.push(format!("{} free", format_size(free_space)));
+    }
+    if !details.is_empty() {
+        println!("    {}", details.join(", "));
+    }
+
+    for issue in &diagnosis.issues {
+        match issue.severity() {
+            Severity::Warning => println!("    \x1b[93mwarning\x1b[0m: {issue}"),
+            Severity::Error => println!("    \x1b[91merror\x1b[0m: {issue}"),
+        }
+    }
+}
+
+fn doctor(builder: &project_dirs_builder::Builder, entry: Option<&str>, strict: bool, json: bool) {
+    let result = match builder.doctor(entry) {
+        Ok(result) => result,
+        Err(error) => {
+            eprintln!("\x1b[93mERROR: Failed to resolve directories\x1b[0m");
+            eprintln!("   {}", error);
+            std::process::exit(1);
+        }
+    };
+
+    if json {
+        print_json(&result);
+    } else {
+        for (name, report) in &result.reports {
+            for (dir, diagnosis) in &report.directories {
+                print_diagnosis(name, dir, diagnosis);
+            }
+        }
+        for (name, error) in &result.errors {
+            println!("{name}");
+            println!("    \x1b[91merror\x1b[0m: failed to resolve directories: {error}");
+        }
+    }
+
+    let warnings = |report: &DoctorReport| report.issues().next().is_some();
+    if result.has_problems() || (strict && result.reports.values().any(warnings)) {
+        std::process::exit(1);
+    }
 }
 
 fn main() {
@@ -42,6 +353,11 @@ fn main() {
             organization: String::new(),
             application: String::new(),
             custom_env: Default::default(),
+            custom_directories: Vec::new(),
+            version: None,
+            naming_mode: Default::default(),
+            instance: None,
+            ownership: None,
             spec: project_dirs_builder::Spec::SystemDefault,
         })
         .unwrap()
@@ -100,13 +416,32 @@ fn main() {
         builder.qualifier = qualifier;
     }
 
-    let result = serde_json::to_string_pretty(&builder.build());
-
-    match result {
-        Ok(r) => println!("{r}"),
-        Err(e) => {
-            eprintln!("UNEXPECTED ERROR: Failed to serialize result: {e:?}");
-            std::process::exit(1);
+    match cli.command {
+        Some(Command::Relocate {
+            from,
+            to,
+            dry_run,
+            symlinks,
+        }) => relocate(&builder, &from, &to, dry_run, symlinks),
+        Some(Command::SystemdUnit { entry, modes, json }) => {
+            systemd_unit(&builder, entry.as_deref(), modes, json)
+        }
+        Some(Command::Tmpfiles { entry, ownership }) => {
+            let policy = ownership_policy(&builder, ownership);
+            tmpfiles(&builder, entry.as_deref(), policy)
+        }
+        Some(Command::Sysusers { ownership }) => {
+            let policy = ownership_policy(&builder, ownership);
+            let description = builder.project().application_name().to_string();
+            sysusers_entries(&policy, &description)
+                .iter()
+                .for_each(|line| println!("{line}"))
         }
+        Some(Command::Doctor {
+            entry,
+            strict,
+            json,
+        }) => doctor(&builder, entry.as_deref(), strict, json),
+        None => print_json(&builder.build()),
     }
 }
@@ -1,6 +1,32 @@
-use project_dirs::{Directory, ProjectDirs};
+use project_dirs::dir_utils::ProvisionPolicy;
+use project_dirs::doctor::{Doctor, DoctorReport};
+use project_dirs::env::{EnvSource, SystemEnv};
+use project_dirs::migration::RelocationPlan;
+use project_dirs::strategy::StrategyRegistry;
+use project_dirs::strategy::systemd::{
+    OwnershipPolicy, ServiceDirectives, TmpfilesEntry, sysusers_entries, tmpfiles_entries,
+};
+use project_dirs::strategy::xdg::HOME;
+use project_dirs::{CustomDirectory, Directory, EnvOverrides, NamingMode, ProjectDirs};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, path::PathBuf};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+mod template;
+
+pub use template::{Template, TemplateError};
+
+/// Message of the error followed by its sources, e.g.
+/// `Current working directory cannot be read: No such file or directory`
+fn error_message(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(error) = source {
+        message.push_str(&format!(": {error}"));
+        source = error.source();
+    }
+    message
+}
 
 fn default_true() -> bool {
     true
@@ -70,12 +96,41 @@ pub enum Strategy {
     CurrentSystem,
     /// Get directories using FHS standard
     Fhs(#[serde(default)] Option<Fhs>),
+    /// Get directories using FreeBSD hier(7) for ports and packages
+    Bsd,
+    /// Get directories passed by systemd (`$STATE_DIRECTORY` etc.). Missing ones fall back to FHS
+    Systemd,
+    /// Get directories of the Flatpak or Snap sandbox. Fails outside of the sandbox
+    Sandbox,
+    /// Get portable directories next to the executable (`<exe_dir>/<app>-data`)
+    Portable,
     /// Get directories using XDG standard
     Xdg,
     /// Get directories using unix-style directory
     Unix(Unix),
     /// Get directories for windows
     Windows(Windows),
+    /// Use strategy registered by name in the [`StrategyRegistry`]. See
+    /// [`Builder::build_with_registry`]
+    Registered(String),
+    /// Get directories from path templates, e.g. `{XDG_STATE_HOME:-{HOME}/.local/state}/{application}/log`.
+    /// Available placeholders: `{application}`, `{organization}`, `{qualifier}`, `{dir:<directory>}`
+    /// and env variables. `{NAME:-default}` provides default value
+    Template(HashMap<Directory, String>),
+}
+
+impl Strategy {
+    /// Whether the strategy resolves system directories (FHS, BSD hier(7) or systemd), so
+    /// `tmpfiles.d` entries are generated for it
+    pub fn is_system_scope(&self) -> bool {
+        match self {
+            Strategy::Fhs(_) | Strategy::Bsd | Strategy::Systemd => true,
+            Strategy::Registered(name) => {
+                matches!(name.as_str(), "fhs" | "fhs-local" | "bsd" | "systemd")
+            }
+            _ => false,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
@@ -109,6 +164,11 @@ pub struct SpecEntry {
 
     #[serde(default)]
     pub mountpoint: Option<PathBuf>,
+
+    /// Override single directories with the variables from the `custom_env`, e.g.
+    /// `MY_APP_DATA_DIR`. See [`EnvOverrides`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env_overrides: Option<EnvOverrides>,
 }
 
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
@@ -142,6 +202,42 @@ pub struct CustomEnv {
     pub allow_variable_clearing: bool,
 }
 
+impl EnvSource for CustomEnv {
+    fn var(&self, name: &str) -> Option<String> {
+        match self.env.get(name) {
+            Some(Some(value)) if !value.is_empty() => Some(value.clone()),
+            Some(_) if self.allow_variable_clearing => None,
+            _ if self.fallback_to_system => SystemEnv.var(name),
+            _ => None,
+        }
+    }
+
+    fn home_dir(&self) -> Option<PathBuf> {
+        match self.env.get(HOME) {
+            Some(Some(value)) if !value.is_empty() => Some(PathBuf::from(value)),
+            Some(_) if self.allow_variable_clearing => None,
+            _ if self.fallback_to_system => SystemEnv.home_dir(),
+            _ => None,
+        }
+    }
+
+    fn current_dir(&self) -> std::io::Result<PathBuf> {
+        SystemEnv.current_dir()
+    }
+
+    fn current_exe(&self) -> std::io::Result<PathBuf> {
+        SystemEnv.current_exe()
+    }
+
+    fn uid(&self) -> Option<u32> {
+        SystemEnv.uid()
+    }
+
+    fn hostname(&self) -> Option<String> {
+        SystemEnv.hostname()
+    }
+}
+
 impl Default for CustomEnv {
     fn default() -> Self {
         CustomEnv {
@@ -153,7 +249,10 @@ impl Default for CustomEnv {
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
+// Derived (de)serialization is the inherent `Builder::deserialize`, wrapped with the validation
+#[serde(remote = "Self")]
 #[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schemars", schemars(rename = "Builder"))]
 pub struct Builder {
     pub qualifier: String,
     pub organization: String,
@@ -166,6 +265,30 @@ pub struct Builder {
     /// **NOTE**: It does only work for custom spec builders
     #[serde(default)]
     pub custom_env: CustomEnv,
+
+    /// Application-defined directory types, e.g. `plugins` derived from `data`
+    #[serde(default)]
+    pub custom_directories: Vec<CustomDirectory>,
+
+    /// Application version. Config, data and state dirs get the version subdirectory, e.g.
+    /// `data/<app>/v2`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+
+    /// Handling of the non-ASCII characters in the organization and application names
+    #[serde(default)]
+    pub naming_mode: NamingMode,
+
+    /// Named instance, e.g. `blue` for `app@blue`. Data, log, runtime and state dirs get the
+    /// instance subdirectory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+
+    /// Owner and modes of the system directories. When set, result contains `tmpfiles.d` lines
+    /// for each resolved spec entry with the system strategy (see [`Strategy::is_system_scope`];
+    /// only `system` for the system default spec) and `sysusers.d` lines
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ownership: Option<OwnershipPolicy>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
@@ -173,6 +296,51 @@ pub struct Builder {
 pub struct BuilderResult {
     pub application_name: String,
     pub dirs: HashMap<String, ProjectDirs>,
+
+    /// Spec entries that failed to resolve (their dirs are empty) with the error message
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub errors: HashMap<String, String>,
+
+    /// `tmpfiles.d` lines of the spec entries. See [`Builder::ownership`]
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub tmpfiles: HashMap<String, Vec<String>>,
+
+    /// `sysusers.d` lines of the service user. See [`Builder::ownership`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sysusers: Vec<String>,
+}
+
+impl Serialize for Builder {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Builder::serialize(self, serializer)
+    }
+}
+
+/// Fails when the manifest is not valid (see [`Builder::validate`])
+impl<'de> Deserialize<'de> for Builder {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let builder = Builder::deserialize(deserializer)?;
+        builder.validate().map_err(serde::de::Error::custom)?;
+        Ok(builder)
+    }
+}
+
+/// Result of the [`Builder::doctor`]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct DoctorResult {
+    pub reports: BTreeMap<String, DoctorReport>,
+
+    /// Spec entries that failed to resolve (and could not be diagnosed) with the error message
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub errors: BTreeMap<String, String>,
+}
+
+impl DoctorResult {
+    /// Whether any of the entries failed to resolve or any of the directories cannot be used
+    pub fn has_problems(&self) -> bool {
+        !self.errors.is_empty() || self.reports.values().any(DoctorReport::has_problems)
+    }
 }
 
 impl Builder {
@@ -189,64 +357,57 @@ impl Builder {
         &self,
         project: &project_dirs::Project,
         entry: &SpecEntry,
-    ) -> ProjectDirs {
+    ) -> project_dirs::Result<ProjectDirs> {
+        self.process_spec_entry_with_registry(project, entry, &StrategyRegistry::with_defaults())
+    }
+
+    /// Same as [`Builder::process_spec_entry`], but named strategies are taken from the given
+    /// registry
+    pub fn process_spec_entry_with_registry(
+        &self,
+        project: &project_dirs::Project,
+        entry: &SpecEntry,
+        registry: &StrategyRegistry,
+    ) -> project_dirs::Result<ProjectDirs> {
         use project_dirs::dir_utils::{Filter as _, Mounted as _};
-        use project_dirs::strategy::fhs::Fhs as _;
         use project_dirs::strategy::unix::Unix as _;
         use project_dirs::strategy::windows::{Windows as _, WindowsEnv};
-        use project_dirs::strategy::xdg::{Xdg as _, XdgEnv};
+
+        let registered = |name: &str| registry.resolve(name, project, &self.custom_env);
 
         let mut pd: ProjectDirs = match &entry.strategy {
-            Strategy::CurrentLocal => project.project_dirs().local,
-            Strategy::CurrentUser => project.project_dirs().user,
-            Strategy::CurrentSystem => project.project_dirs().system,
+            Strategy::CurrentLocal => registered("current-local"),
+            Strategy::CurrentUser => registered("current-user"),
+            Strategy::CurrentSystem => registered("current-system"),
             Strategy::Fhs(fhs) => match fhs {
-                Some(Fhs::Local) => project.fhs_local().into(),
-                Some(Fhs::Shared) | None => project.fhs().into(),
+                Some(Fhs::Local) => registered("fhs-local"),
+                Some(Fhs::Shared) | None => registered("fhs"),
             },
+            Strategy::Bsd => registered("bsd"),
+            Strategy::Systemd => registered("systemd"),
+            Strategy::Sandbox => registered("sandbox"),
+            Strategy::Portable => registered("portable"),
             Strategy::Xdg => {
-                let mut env = if self.custom_env.fallback_to_system {
-                    XdgEnv::new_system()
-                } else {
-                    XdgEnv::default()
-                };
-
-                env.extend_with_env(
-                    self.custom_env.env.iter().map(|x| (x.0, x.1.as_ref())),
-                    self.custom_env.allow_variable_clearing,
-                );
-
                 if self.custom_env.fallback_to_system {
-                    project
-                        .xdg_with_env(env)
-                        .map(ProjectDirs::from)
-                        .unwrap_or(ProjectDirs::empty())
+                    registered("xdg")
                 } else {
-                    project.xdg_with_env_exclude_missing(env)
+                    registered("xdg-exclude-missing")
                 }
             }
+            Strategy::Registered(name) => registered(name),
             Strategy::Unix(unix) => match unix {
-                Unix::Pwd => project
-                    .unix_pwd()
-                    .map(Into::into)
-                    .unwrap_or(ProjectDirs::empty()),
-                Unix::Home => project
-                    .unix_home()
-                    .map(Into::into)
-                    .unwrap_or(ProjectDirs::empty()),
-                Unix::Binary => project
-                    .unix_binary()
-                    .map(Into::into)
-                    .unwrap_or(ProjectDirs::empty()),
+                Unix::Pwd => registered("unix-pwd"),
+                Unix::Home => registered("unix-home"),
+                Unix::Binary => registered("unix-binary"),
                 Unix::Custom {
                     path,
                     prefix,
                     skip_project_addition,
-                } => match (skip_project_addition, prefix) {
+                } => Ok(match (skip_project_addition, prefix) {
                     (true, _) => project_dirs::strategy::unix::unix(path).into(),
                     (false, Some(prefix)) => project.unix_prefixed(path, prefix).into(),
                     (false, None) => project.unix(path).into(),
-                },
+                }),
             },
             Strategy::Windows(windows) => {
                 #[cfg(target_os = "windows")]
@@ -264,13 +425,26 @@ impl Builder {
                     self.custom_env.allow_variable_clearing,
                 );
 
-                match windows {
+                Ok(match windows {
                     Windows::Standard => project.windows_user_with_env(env),
                     Windows::Local => project.windows_user_local_with_env(env),
                     Windows::Shared => project.windows_user_shared_with_env(env),
                     Windows::System => project.windows_system_with_env(env),
-                }
+                })
             }
+            Strategy::Template(templates) => {
+                Template::new(project, templates, |name| self.custom_env.var(name))
+                    .render_all()
+                    .map_err(project_dirs::Error::from)
+            }
+        }?;
+
+        pd = match &entry.env_overrides {
+            Some(overrides) => project
+                .clone()
+                .with_env_overrides(overrides.clone())
+                .finalize_project_dirs_with_env(pd, &self.custom_env),
+            None => project.finalize_project_dirs_with_env(pd, &self.custom_env),
         };
 
         if let Some(filter) = &entry.filter {
@@ -295,24 +469,200 @@ impl Builder {
             );
         }
 
-        pd
+        Ok(pd)
     }
 
     pub fn build(&self) -> BuilderResult {
-        let project =
-            project_dirs::Project::new(&self.qualifier, &self.organization, &self.application);
+        self.build_with_registry(&StrategyRegistry::with_defaults())
+    }
+
+    /// Take directories of the spec entry from the built result
+    fn take_entry(built: &mut BuilderResult, name: &str) -> project_dirs::Result<ProjectDirs> {
+        match built.errors.remove(name) {
+            Some(error) => Err(project_dirs::Error::custom(format!("{name}: {error}"))),
+            None => built
+                .dirs
+                .remove(name)
+                .ok_or_else(|| project_dirs::Error::custom(format!("Unknown spec entry: {name}"))),
+        }
+    }
+
+    /// Plan moving directories between two spec entries, e.g. from `~/.app` to XDG. Use
+    /// [`RelocationPlan::execute`] to move them
+    pub fn relocation_plan(&self, from: &str, to: &str) -> project_dirs::Result<RelocationPlan> {
+        let mut built = self.build();
+
+        let source = Self::take_entry(&mut built, from)?;
+        let target = Self::take_entry(&mut built, to)?;
+        Ok(RelocationPlan::new(&self.project(), &source, &target))
+    }
+
+    /// Diagnose directories of the spec entry or of all entries. Entries that failed to
+    /// resolve are reported in the [`DoctorResult::errors`] (or returned as error when
+    /// requested explicitly)
+    pub fn doctor(&self, entry: Option<&str>) -> project_dirs::Result<DoctorResult> {
+        let mut built = self.build();
+
+        match entry {
+            Some(name) => {
+                let dirs = Self::take_entry(&mut built, name)?;
+                Ok(DoctorResult {
+                    reports: BTreeMap::from([(name.to_string(), dirs.diagnose())]),
+                    errors: BTreeMap::new(),
+                })
+            }
+            None => Ok(DoctorResult {
+                reports: built
+                    .dirs
+                    .into_iter()
+                    .filter(|(name, _)| !built.errors.contains_key(name))
+                    .map(|(name, dirs)| (name, dirs.diagnose()))
+                    .collect(),
+                errors: built.errors.into_iter().collect(),
+            }),
+        }
+    }
+
+    /// Directories of the spec entry or FHS system scope of the project
+    fn entry_or_fhs(&self, entry: Option<&str>) -> project_dirs::Result<ProjectDirs> {
+        use project_dirs::strategy::fhs::Fhs as _;
+
+        match entry {
+            Some(name) => Self::take_entry(&mut self.build(), name),
+            None => {
+                let project = self.project();
+                Ok(project.finalize_project_dirs(project.fhs().into()))
+            }
+        }
+    }
 
+    /// Systemd `[Service]` directives for the spec entry. Uses FHS system scope of the project
+    /// when entry is not given (see [`ServiceDirectives::for_project`])
+    pub fn service_directives(
+        &self,
+        entry: Option<&str>,
+        policy: &ProvisionPolicy,
+    ) -> project_dirs::Result<ServiceDirectives> {
+        match entry {
+            Some(_) => Ok(ServiceDirectives::new(&self.entry_or_fhs(entry)?, policy)),
+            None => Ok(ServiceDirectives::for_project(&self.project(), policy)),
+        }
+    }
+
+    /// `tmpfiles.d` entries for the spec entry. Uses FHS system scope of the project when entry
+    /// is not given
+    pub fn tmpfiles(
+        &self,
+        entry: Option<&str>,
+        policy: &OwnershipPolicy,
+    ) -> project_dirs::Result<Vec<TmpfilesEntry>> {
+        Ok(tmpfiles_entries(&self.entry_or_fhs(entry)?, policy))
+    }
+
+    /// Check names of the custom directories and whether all of the [`Directory::Custom`] used
+    /// in the manifest are declared in the `custom_directories`, so typos (e.g. `confg`) are
+    /// not taken as the custom directories
+    pub fn validate(&self) -> project_dirs::Result<()> {
+        for custom in &self.custom_directories {
+            custom.validate()?;
+        }
+
+        let mut used: Vec<&Directory> = self
+            .custom_directories
+            .iter()
+            .map(|custom| &custom.base)
+            .collect();
+        if let Spec::Custom(items) = &self.spec {
+            for entry in items.values() {
+                used.extend(&entry.directories);
+                if let Strategy::Template(templates) = &entry.strategy {
+                    used.extend(templates.keys());
+                }
+            }
+        }
+        if let Some(ownership) = &self.ownership {
+            used.extend(ownership.modes.modes.keys());
+        }
+
+        let declared = |name: &str| self.custom_directories.iter().any(|c| c.name == name);
+        match used.into_iter().find_map(|dir| match dir {
+            Directory::Custom(name) if !declared(name) => Some(name),
+            _ => None,
+        }) {
+            Some(name) => Err(project_dirs::Error::InvalidCustomDirectory {
+                name: name.clone(),
+                reason: "unknown directory, declare it in the custom_directories".to_string(),
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Project described by the manifest (with naming mode, version and custom directories)
+    pub fn project(&self) -> project_dirs::Project {
+        let mut project = self.custom_directories.iter().fold(
+            project_dirs::Project::new(&self.qualifier, &self.organization, &self.application)
+                .with_naming_mode(self.naming_mode),
+            |project, custom| project.with_custom_directory(custom.clone()),
+        );
+
+        if let Some(version) = &self.version {
+            project = project.with_version(version);
+        }
+        if let Some(instance) = &self.instance {
+            project = project.with_instance(instance);
+        }
+        project
+    }
+
+    /// Build using named strategies from the given registry. Allows to use own strategies in
+    /// the manifest (see [`Strategy::Registered`])
+    pub fn build_with_registry(&self, registry: &StrategyRegistry) -> BuilderResult {
+        let project = self.project();
         let application_name = project.application_name().to_string();
+        let mut errors = HashMap::new();
+
+        let dirs = match &self.spec {
+            Spec::SystemDefault => self.system_default(&project),
+            Spec::Custom(items) => items.iter().fold(HashMap::new(), |mut acc, item| {
+                let pd = self
+                    .process_spec_entry_with_registry(&project, item.1, registry)
+                    .unwrap_or_else(|error| {
+                        errors.insert(item.0.clone(), error_message(&error));
+                        ProjectDirs::empty()
+                    });
+                acc.insert(item.0.clone(), pd);
+                acc
+            }),
+        };
+
+        let (tmpfiles, sysusers) = match &self.ownership {
+            Some(policy) => (
+                dirs.iter()
+                    .filter(|(name, _)| match &self.spec {
+                        Spec::SystemDefault => name.as_str() == "system",
+                        Spec::Custom(items) => {
+                            !errors.contains_key(*name) && items[*name].strategy.is_system_scope()
+                        }
+                    })
+                    .map(|(name, pd)| {
+                        let lines = tmpfiles_entries(pd, policy)
+                            .iter()
+                            .map(ToString::to_string)
+                            .collect();
+                        (name.clone(), lines)
+                    })
+                    .collect(),
+                sysusers_entries(policy, &application_name),
+            ),
+            None => (HashMap::new(), Vec::new()),
+        };
 
         BuilderResult {
             application_name,
-            dirs: match &self.spec {
-                Spec::SystemDefault => self.system_default(&project),
-                Spec::Custom(items) => items.iter().fold(HashMap::new(), |mut acc, item| {
-                    acc.insert(item.0.clone(), self.process_spec_entry(&project, item.1));
-                    acc
-                }),
-            },
+            dirs,
+            errors,
+            tmpfiles,
+            sysusers,
         }
     }
 }
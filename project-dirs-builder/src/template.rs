@@ -0,0 +1,184 @@
+use project_dirs::{Directory, Project, ProjectDirs};
+use serde::Deserialize;
+use std::{collections::HashMap, path::PathBuf};
+
+/// Error of the template rendering
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    /// Placeholder is not closed with `}`
+    Unclosed(String),
+    /// Variable or directory cannot be resolved and there is no default value
+    Unresolved(String),
+    /// Directory template references itself (directly or not)
+    Cycle(Directory),
+}
+
+impl std::fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unclosed(rest) => write!(f, "Unclosed template placeholder: {rest}"),
+            Self::Unresolved(name) => write!(f, "Cannot resolve template placeholder: {name}"),
+            Self::Cycle(dir) => write!(
+                f,
+                "Template of the {} directory references itself",
+                dir.name()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+impl From<TemplateError> for project_dirs::Error {
+    fn from(error: TemplateError) -> Self {
+        project_dirs::Error::custom(error.to_string())
+    }
+}
+
+/// Renders path templates like `{XDG_STATE_HOME:-{HOME}/.local/state}/{application}/log`
+///
+/// Placeholders:
+/// * `{application}`, `{organization}`, `{qualifier}` - project triplet (after cleanup)
+/// * `{dir:<directory>}` - other directory from the same templates, e.g. `{dir:data}`
+/// * `{VARIABLE}` - any other name is resolved as env variable
+/// * `{NAME:-default}` - use default (that is a template itself) if `NAME` cannot be resolved
+pub struct Template<'a, F: Fn(&str) -> Option<String>> {
+    project: &'a Project,
+    templates: &'a HashMap<Directory, String>,
+    env: F,
+}
+
+const DEFAULT_SEPARATOR: &str = ":-";
+const DIR_PREFIX: &str = "dir:";
+
+impl<'a, F: Fn(&str) -> Option<String>> Template<'a, F> {
+    pub fn new(project: &'a Project, templates: &'a HashMap<Directory, String>, env: F) -> Self {
+        Self {
+            project,
+            templates,
+            env,
+        }
+    }
+
+    /// Render all directories. Fails with the error of the first directory (in the
+    /// [`Directory`] order) that cannot be rendered
+    pub fn render_all(&self) -> Result<ProjectDirs, TemplateError> {
+        let mut dirs: Vec<_> = self.templates.keys().collect();
+        dirs.sort();
+
+        dirs.into_iter()
+            .map(|dir| Ok((dir.clone(), self.render_dir(dir)?)))
+            .collect::<Result<_, _>>()
+            .map(ProjectDirs::new)
+    }
+
+    /// Render a single directory
+    pub fn render_dir(&self, dir: &Directory) -> Result<PathBuf, TemplateError> {
+        self.render_dir_inner(dir, &mut Vec::new())
+    }
+
+    /// Render any template string
+    pub fn render(&self, template: &str) -> Result<String, TemplateError> {
+        self.render_inner(template, &mut Vec::new())
+    }
+
+    fn render_dir_inner(
+        &self,
+        dir: &Directory,
+        stack: &mut Vec<Directory>,
+    ) -> Result<PathBuf, TemplateError> {
+        if stack.contains(dir) {
+            return Err(TemplateError::Cycle(dir.clone()));
+        }
+
+        let template = self
+            .templates
+            .get(dir)
+            .ok_or_else(|| TemplateError::Unresolved(format!("{DIR_PREFIX}{}", dir.name())))?;
+
+        stack.push(dir.clone());
+        let result = self.render_inner(template, stack);
+        stack.pop();
+
+        result.map(PathBuf::from)
+    }
+
+    fn render_inner(
+        &self,
+        template: &str,
+        stack: &mut Vec<Directory>,
+    ) -> Result<String, TemplateError> {
+        let mut result = String::new();
+        let mut rest = template;
+
+        while let Some(start) = rest.find('{') {
+            result.push_str(&rest[..start]);
+
+            let end = matching_brace(&rest[start..])
+                .ok_or_else(|| TemplateError::Unclosed(rest[start..].to_string()))?;
+            let body = &rest[start + 1..start + end];
+
+            result.push_str(&self.render_placeholder(body, stack)?);
+            rest = &rest[start + end + 1..];
+        }
+
+        result.push_str(rest);
+        Ok(result)
+    }
+
+    fn render_placeholder(
+        &self,
+        body: &str,
+        stack: &mut Vec<Directory>,
+    ) -> Result<String, TemplateError> {
+        let (name, default) = match body.split_once(DEFAULT_SEPARATOR) {
+            Some((name, default)) => (name, Some(default)),
+            None => (body, None),
+        };
+
+        match (self.resolve(name, stack), default) {
+            (Err(TemplateError::Unresolved(_)), Some(default)) => self.render_inner(default, stack),
+            (other, _) => other,
+        }
+    }
+
+    fn resolve(&self, name: &str, stack: &mut Vec<Directory>) -> Result<String, TemplateError> {
+        if let Some(dir_name) = name.strip_prefix(DIR_PREFIX) {
+            let dir = Directory::deserialize(serde::de::value::StrDeserializer::<
+                serde::de::value::Error,
+            >::new(dir_name))
+            .map_err(|_| TemplateError::Unresolved(name.to_string()))?;
+
+            return self
+                .render_dir_inner(&dir, stack)
+                .map(|p| p.to_string_lossy().to_string());
+        }
+
+        match name {
+            "application" => Ok(self.project.application_name().to_string()),
+            "organization" => Ok(self.project.organization_name().to_string()),
+            "qualifier" => Ok(self.project.qualifier().to_string()),
+            _ => (self.env)(name)
+                .filter(|value| !value.is_empty())
+                .ok_or_else(|| TemplateError::Unresolved(name.to_string())),
+        }
+    }
+}
+
+/// Get index of the `}` matching the `{` at the beginning of the input
+fn matching_brace(input: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in input.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i);
+                }
+            }
+            _ => (),
+        }
+    }
+    None
+}
@@ -14,6 +14,10 @@ typedef uint8_t project_dirs__FromBuilderError;
 
 char *project_dirs__from_builder(const char *s, project_dirs__FromBuilderError *error);
 
+/**
+ * Get project directories for the current system as JSON. Returns NULL if any of the
+ * components is empty or invalid
+ */
 char *project_dirs__project_dirs(const char *application,
                                  const char *organization,
                                  const char *qualifier);
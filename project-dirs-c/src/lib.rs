@@ -37,6 +37,8 @@ pub unsafe extern "C" fn project_dirs__from_builder(
     }
 }
 
+/// Get project directories for the current system as JSON. Returns NULL if any of the
+/// components is empty or invalid
 #[unsafe(no_mangle)]
 pub unsafe extern "C" fn project_dirs__project_dirs(
     application: *const c_char,
@@ -50,8 +52,16 @@ pub unsafe extern "C" fn project_dirs__project_dirs(
             .unwrap_or("");
         let qualifier = std::ffi::CStr::from_ptr(qualifier).to_str().unwrap_or("");
 
-        let project = project_dirs::Project::new(application, organization, qualifier);
-        let dirs = project.project_dirs();
+        let project_id = project_dirs::ProjectId::builder()
+            .qualifier(qualifier)
+            .organization(organization)
+            .application(application)
+            .build();
+
+        let Ok(project_id) = project_id else {
+            return std::ptr::null_mut();
+        };
+        let dirs = project_id.project().project_dirs();
 
         let result = CString::new(serde_json::to_string(&dirs).unwrap()).unwrap();
         let result_ptr = result.as_ptr() as *mut c_char;
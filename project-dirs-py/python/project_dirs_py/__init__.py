@@ -1,5 +1,5 @@
-from dataclasses import dataclass
-from typing import Dict, Any, Optional
+from dataclasses import dataclass, field
+from typing import Dict, Any, List, Optional
 import json
 from pathlib import Path
 import project_dirs_py._project_dirs_rust as _base
@@ -19,6 +19,20 @@ class ProjectDirs:
     project_root: Optional[Path]
     runtime: Optional[Path]
     state: Optional[Path]
+    custom: Dict[str, Path]
+
+    _BUILTIN = {
+        "bin",
+        "cache",
+        "config",
+        "data",
+        "include",
+        "lib",
+        "log",
+        "project-root",
+        "runtime",
+        "state",
+    }
 
     @classmethod
     def _from_str_dict(cls, d: Dict[str, str]) -> "ProjectDirs":
@@ -33,18 +47,25 @@ class ProjectDirs:
             project_root=Path(d["project_root"]) if "project_root" in d else None,
             runtime=Path(d["runtime"]) if "runtime" in d else None,
             state=Path(d["state"]) if "state" in d else None,
+            custom={k: Path(v) for k, v in d.items() if k not in cls._BUILTIN},
         )
 
 @dataclass
 class BuilderResult:
     application_name: str
     dirs: Dict[str, "ProjectDirs"]
+    errors: Dict[str, str] = field(default_factory=dict)
+    tmpfiles: Dict[str, List[str]] = field(default_factory=dict)
+    sysusers: List[str] = field(default_factory=list)
 
     @classmethod
     def _from_str_dict(cls, d: Dict[str, Any]) -> "BuilderResult":
         return BuilderResult(
             application_name=d["application_name"],
             dirs={k: ProjectDirs._from_str_dict(v) for k, v in d["dirs"].items()},
+            errors=d.get("errors", {}),
+            tmpfiles=d.get("tmpfiles", {}),
+            sysusers=d.get("sysusers", []),
         )
 
     @classmethod
@@ -1,6 +1,7 @@
+use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 
-use crate::{FullProjectDirs, ProjectDirs};
+use crate::{Directory, FullProjectDirs, ProjectDirs, Scoped};
 
 /// Filter project directories
 pub trait Filter {
@@ -29,7 +30,7 @@ impl Filter for ProjectDirs {
             self.0
                 .iter()
                 .filter(|(_, p)| p.is_dir())
-                .map(|x| (*x.0, x.1.clone()))
+                .map(|x| (x.0.clone(), x.1.clone()))
                 .collect(),
         )
     }
@@ -39,7 +40,7 @@ impl Filter for ProjectDirs {
             self.0
                 .iter()
                 .filter(|(_, p)| p.exists() && !p.is_dir())
-                .map(|x| (*x.0, x.1.clone()))
+                .map(|x| (x.0.clone(), x.1.clone()))
                 .collect(),
         )
     }
@@ -49,7 +50,7 @@ impl Filter for ProjectDirs {
             self.0
                 .iter()
                 .filter(|(_, p)| p.try_exists().map(|e| !e).unwrap_or(false))
-                .map(|x| (*x.0, x.1.clone()))
+                .map(|x| (x.0.clone(), x.1.clone()))
                 .collect(),
         )
     }
@@ -59,7 +60,7 @@ impl Filter for ProjectDirs {
             self.0
                 .iter()
                 .filter(|(_, p)| p.try_exists().is_err())
-                .map(|x| (*x.0, x.1.clone()))
+                .map(|x| (x.0.clone(), x.1.clone()))
                 .collect(),
         )
     }
@@ -69,7 +70,7 @@ impl Filter for ProjectDirs {
             self.0
                 .iter()
                 .filter(|(_, p)| !p.is_dir())
-                .map(|x| (*x.0, x.1.clone()))
+                .map(|x| (x.0.clone(), x.1.clone()))
                 .collect(),
         )
     }
@@ -114,6 +115,155 @@ impl Mounted for FullProjectDirs {
     }
 }
 
+/// Unix mode policy used by [`Provision`]. Non-unix systems ignore modes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct ProvisionPolicy {
+    /// Mode used for directories without explicit entry in `modes`
+    pub default_mode: u32,
+    /// Per-directory modes
+    pub modes: HashMap<Directory, u32>,
+}
+
+impl Default for ProvisionPolicy {
+    /// `0o755` for everything, except [`Directory::Runtime`] that is `0o700` (required by XDG)
+    fn default() -> Self {
+        Self {
+            default_mode: 0o755,
+            modes: HashMap::from([(Directory::Runtime, 0o700)]),
+        }
+    }
+}
+
+impl ProvisionPolicy {
+    /// Set mode for the given directory type. E.g. `0o700` for [`Directory::Config`] with secrets
+    pub fn with_mode(mut self, dir: Directory, mode: u32) -> Self {
+        self.modes.insert(dir, mode);
+        self
+    }
+
+    /// Get mode for the given directory type
+    pub fn mode(&self, dir: &Directory) -> u32 {
+        self.modes.get(dir).copied().unwrap_or(self.default_mode)
+    }
+}
+
+/// Result of the [`Provision::provision`]. Uses the same classification as [`Filter`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct ProvisionReport {
+    /// Directories created by the provisioning
+    pub created: ProjectDirs,
+    /// Directories that were already present. Their mode is left untouched
+    pub existing: ProjectDirs,
+    /// Non-directory entries in places where directories are expected (also in parent paths)
+    pub non_dirs: ProjectDirs,
+    /// Directories that could not be checked or created. E.g. no write access to the parent
+    pub denied: ProjectDirs,
+}
+
+/// Provisioning report for each of the [`Scoped`] scopes
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct ScopedProvisionReport {
+    pub user: ProvisionReport,
+    pub system: ProvisionReport,
+    pub local: ProvisionReport,
+}
+
+/// Create missing project directories
+pub trait Provision {
+    type Report;
+
+    /// Create missing directories (with parents) and apply modes from the policy to the newly
+    /// created ones
+    fn provision(&self, policy: &ProvisionPolicy) -> Self::Report;
+}
+
+#[cfg(unix)]
+pub(crate) fn create_dir_with_mode(path: &Path, mode: u32) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::create_dir_all(path)?;
+    // Set explicitly, as umask applies to DirBuilder modes
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn create_dir_with_mode(path: &Path, _mode: u32) -> std::io::Result<()> {
+    std::fs::create_dir_all(path)
+}
+
+impl Provision for ProjectDirs {
+    type Report = ProvisionReport;
+
+    fn provision(&self, policy: &ProvisionPolicy) -> ProvisionReport {
+        let mut report = ProvisionReport {
+            created: ProjectDirs::empty(),
+            existing: ProjectDirs::empty(),
+            non_dirs: ProjectDirs::empty(),
+            denied: ProjectDirs::empty(),
+        };
+        // Some strategies use the same path for multiple directories (e.g. config and project
+        // root)
+        let mut created_paths = HashSet::<PathBuf>::new();
+
+        // Shallowest first, so parents (e.g. config in the unix layout) are created with their
+        // own mode instead of being created implicitly by `create_dir_all` of the children
+        let mut dirs: Vec<_> = self.0.iter().collect();
+        dirs.sort_by_key(|(dir, path)| (path.components().count(), *dir));
+
+        for (dir, path) in dirs {
+            let target = if created_paths.contains(path) {
+                &mut report.created
+            } else {
+                match path.try_exists() {
+                    Err(_) => &mut report.denied,
+                    Ok(true) if path.is_dir() => &mut report.existing,
+                    Ok(true) => &mut report.non_dirs,
+                    Ok(false) => match create_dir_with_mode(path, policy.mode(dir)) {
+                        Ok(()) => {
+                            created_paths.insert(path.clone());
+                            &mut report.created
+                        }
+                        Err(e) if e.kind() == std::io::ErrorKind::NotADirectory => {
+                            &mut report.non_dirs
+                        }
+                        Err(_) => &mut report.denied,
+                    },
+                }
+            };
+            target.0.insert(dir.clone(), path.clone());
+        }
+
+        report
+    }
+}
+
+impl Provision for FullProjectDirs {
+    type Report = ProvisionReport;
+
+    fn provision(&self, policy: &ProvisionPolicy) -> ProvisionReport {
+        ProjectDirs::from(self.clone()).provision(policy)
+    }
+}
+
+impl Provision for Scoped {
+    type Report = ScopedProvisionReport;
+
+    fn provision(&self, policy: &ProvisionPolicy) -> ScopedProvisionReport {
+        ScopedProvisionReport {
+            user: self.user.provision(policy),
+            system: self.system.provision(policy),
+            local: self.local.provision(policy),
+        }
+    }
+}
+
 /// Retrive home directory
 pub fn home_dir() -> Option<PathBuf> {
     #[cfg(feature = "nonstd_home_dir")]
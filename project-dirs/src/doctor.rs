@@ -0,0 +1,404 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::env::{EnvSource, SystemEnv};
+use crate::{Directory, FullProjectDirs, ProjectDirs, Scoped};
+
+/// Memory-backed filesystems. Content is lost on reboot
+const VOLATILE_FILESYSTEMS: [&str; 2] = ["tmpfs", "ramfs"];
+
+/// Network filesystems. Unix sockets and locks may not work there
+const NETWORK_FILESYSTEMS: [&str; 7] = ["nfs", "nfs4", "cifs", "smb2", "smbfs", "afpfs", "webdav"];
+
+/// Type of the fs entry (symlinks are followed)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum EntryKind {
+    Directory,
+    File,
+    /// Socket, fifo, device etc.
+    Other,
+}
+
+/// Severity of the [`Issue`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum Severity {
+    /// Directory works, but something looks suspicious
+    Warning,
+    /// Directory cannot be used
+    Error,
+}
+
+/// Problem found by the [`Doctor`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "issue", rename_all = "kebab-case"))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum Issue {
+    /// Directory does not exist (yet). See [`crate::dir_utils::Provision`]
+    Missing,
+    /// Non-directory entry in the place of the directory
+    NotADirectory,
+    /// Symlink pointing to the missing target
+    BrokenSymlink { target: PathBuf },
+    /// Entry cannot be checked, e.g. no execute access to the parent
+    Inaccessible { reason: String },
+    /// Directory is owned by the other user than the current one
+    ForeignOwner { owner: u32, uid: u32 },
+    /// Anyone can write to the directory
+    WorldWritable,
+    /// Persistent directory on the memory-backed filesystem (e.g. `tmpfs`)
+    Volatile { filesystem: String },
+    /// Runtime directory on the network filesystem (e.g. `nfs`)
+    NetworkFilesystem { filesystem: String },
+}
+
+impl Issue {
+    pub fn severity(&self) -> Severity {
+        match self {
+            Issue::NotADirectory | Issue::BrokenSymlink { .. } | Issue::Inaccessible { .. } => {
+                Severity::Error
+            }
+            Issue::Missing
+            | Issue::ForeignOwner { .. }
+            | Issue::WorldWritable
+            | Issue::Volatile { .. }
+            | Issue::NetworkFilesystem { .. } => Severity::Warning,
+        }
+    }
+}
+
+impl std::fmt::Display for Issue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Issue::Missing => write!(f, "does not exist"),
+            Issue::NotADirectory => write!(f, "is not a directory"),
+            Issue::BrokenSymlink { target } => write!(f, "symlink target {target:?} is missing"),
+            Issue::Inaccessible { reason } => write!(f, "cannot be checked: {reason}"),
+            Issue::ForeignOwner { owner, uid } => {
+                write!(f, "is owned by uid {owner}, current uid is {uid}")
+            }
+            Issue::WorldWritable => write!(f, "is world-writable"),
+            Issue::Volatile { filesystem } => {
+                write!(f, "is on {filesystem}, content will be lost on reboot")
+            }
+            Issue::NetworkFilesystem { filesystem } => {
+                write!(f, "is on {filesystem}, sockets and locks may not work")
+            }
+        }
+    }
+}
+
+/// Diagnosis of the single directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct DirectoryDiagnosis {
+    pub path: PathBuf,
+    /// `None` when the entry does not exist or cannot be checked
+    pub kind: Option<EntryKind>,
+    /// Target of the symlink, when the path itself is a symlink
+    pub symlink_target: Option<PathBuf>,
+    /// Uid of the owner. Always `None` on non-unix systems
+    pub owner: Option<u32>,
+    /// Permission bits (e.g. `0o755`). Always `None` on non-unix systems
+    pub mode: Option<u32>,
+    /// Filesystem type (e.g. `ext4`, `tmpfs`, `nfs`). Checked on the closest existing parent
+    /// when the directory is missing
+    pub filesystem: Option<String>,
+    /// Bytes available to the unprivileged user. Checked on the closest existing parent when
+    /// the directory is missing
+    pub free_space: Option<u64>,
+    pub issues: Vec<Issue>,
+}
+
+impl DirectoryDiagnosis {
+    /// Diagnose the path used as the given directory type
+    pub fn new(dir: &Directory, path: &Path, uid: Option<u32>) -> Self {
+        let mut diagnosis = Self {
+            path: path.to_path_buf(),
+            kind: None,
+            symlink_target: std::fs::symlink_metadata(path)
+                .ok()
+                .filter(|m| m.file_type().is_symlink())
+                .and_then(|_| std::fs::read_link(path).ok()),
+            owner: None,
+            mode: None,
+            filesystem: None,
+            free_space: None,
+            issues: Vec::new(),
+        };
+
+        match std::fs::metadata(path) {
+            Ok(metadata) => {
+                diagnosis.kind = Some(if metadata.is_dir() {
+                    EntryKind::Directory
+                } else if metadata.is_file() {
+                    EntryKind::File
+                } else {
+                    EntryKind::Other
+                });
+                diagnosis.set_permissions(&metadata, uid);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => match &diagnosis.symlink_target {
+                Some(target) => diagnosis.issues.push(Issue::BrokenSymlink {
+                    target: target.clone(),
+                }),
+                None => diagnosis.issues.push(Issue::Missing),
+            },
+            Err(e) if e.kind() == std::io::ErrorKind::NotADirectory => {
+                diagnosis.issues.push(Issue::NotADirectory)
+            }
+            Err(e) => diagnosis.issues.push(Issue::Inaccessible {
+                reason: e.to_string(),
+            }),
+        }
+
+        if diagnosis.kind.is_some_and(|k| k != EntryKind::Directory) {
+            diagnosis.issues.push(Issue::NotADirectory);
+        }
+
+        if let Some(existing) = path.ancestors().find(|p| p.try_exists().unwrap_or(false)) {
+            diagnosis.filesystem = filesystem_type(existing);
+            diagnosis.free_space = free_space(existing);
+        }
+
+        if let Some(filesystem) = &diagnosis.filesystem {
+            let persistent = !matches!(
+                dir,
+                Directory::Runtime | Directory::Cache | Directory::Custom(_)
+            );
+            if persistent && VOLATILE_FILESYSTEMS.contains(&filesystem.as_str()) {
+                diagnosis.issues.push(Issue::Volatile {
+                    filesystem: filesystem.clone(),
+                });
+            }
+            if *dir == Directory::Runtime && NETWORK_FILESYSTEMS.contains(&filesystem.as_str()) {
+                diagnosis.issues.push(Issue::NetworkFilesystem {
+                    filesystem: filesystem.clone(),
+                });
+            }
+        }
+
+        diagnosis
+    }
+
+    #[cfg(unix)]
+    fn set_permissions(&mut self, metadata: &std::fs::Metadata, uid: Option<u32>) {
+        use std::os::unix::fs::MetadataExt;
+
+        let mode = metadata.mode() & 0o7777;
+        self.owner = Some(metadata.uid());
+        self.mode = Some(mode);
+
+        if let Some(uid) = uid.filter(|uid| *uid != metadata.uid()) {
+            self.issues.push(Issue::ForeignOwner {
+                owner: metadata.uid(),
+                uid,
+            });
+        }
+        if metadata.is_dir() && mode & 0o002 != 0 {
+            self.issues.push(Issue::WorldWritable);
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn set_permissions(&mut self, _metadata: &std::fs::Metadata, _uid: Option<u32>) {}
+
+    /// Whether the directory cannot be used (any [`Severity::Error`] issue)
+    pub fn has_problems(&self) -> bool {
+        self.issues.iter().any(|i| i.severity() == Severity::Error)
+    }
+}
+
+#[cfg(unix)]
+fn c_path(path: &Path) -> Option<std::ffi::CString> {
+    use std::os::unix::ffi::OsStrExt;
+
+    std::ffi::CString::new(path.as_os_str().as_bytes()).ok()
+}
+
+#[cfg(unix)]
+fn free_space(path: &Path) -> Option<u64> {
+    let path = c_path(path)?;
+    let mut stats = std::mem::MaybeUninit::<libc::statvfs>::uninit();
+
+    // SAFETY: path is nul-terminated, stats are read only on success
+    if unsafe { libc::statvfs(path.as_ptr(), stats.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let stats = unsafe { stats.assume_init() };
+    #[allow(clippy::unnecessary_cast)]
+    Some(stats.f_bavail as u64 * stats.f_frsize as u64)
+}
+
+#[cfg(not(unix))]
+fn free_space(_path: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn filesystem_type(path: &Path) -> Option<String> {
+    let path = c_path(path)?;
+    let mut stats = std::mem::MaybeUninit::<libc::statfs>::uninit();
+
+    // SAFETY: path is nul-terminated, stats are read only on success
+    if unsafe { libc::statfs(path.as_ptr(), stats.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let stats = unsafe { stats.assume_init() };
+
+    // Magic numbers are 32-bit, f_type width and sign differ between architectures
+    let name = match stats.f_type as u32 {
+        0x0102_1994 => "tmpfs",
+        0x8584_58f6 => "ramfs",
+        0x6969 => "nfs",
+        0xff53_4d42 => "cifs",
+        0xfe53_4d42 => "smb2",
+        0xef53 => "ext4",
+        0x5846_5342 => "xfs",
+        0x9123_683e => "btrfs",
+        0x2fc1_2fc1 => "zfs",
+        0x794c_7630 => "overlay",
+        0x6573_5546 => "fuse",
+        0x7371_7368 => "squashfs",
+        0x4d44 => "vfat",
+        0x5346_544e => "ntfs",
+        magic => return Some(format!("{magic:#x}")),
+    };
+    Some(name.to_string())
+}
+
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "dragonfly"
+))]
+fn filesystem_type(path: &Path) -> Option<String> {
+    let path = c_path(path)?;
+    let mut stats = std::mem::MaybeUninit::<libc::statfs>::uninit();
+
+    // SAFETY: path is nul-terminated, stats are read only on success
+    if unsafe { libc::statfs(path.as_ptr(), stats.as_mut_ptr()) } != 0 {
+        return None;
+    }
+    let stats = unsafe { stats.assume_init() };
+
+    // SAFETY: f_fstypename is nul-terminated
+    let name = unsafe { std::ffi::CStr::from_ptr(stats.f_fstypename.as_ptr()) };
+    Some(name.to_string_lossy().into_owned())
+}
+
+#[cfg(not(any(
+    target_os = "linux",
+    target_os = "android",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "dragonfly"
+)))]
+fn filesystem_type(_path: &Path) -> Option<String> {
+    None
+}
+
+/// Result of the [`Doctor::diagnose`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct DoctorReport {
+    /// Uid of the current user. Always `None` on non-unix systems
+    pub uid: Option<u32>,
+    pub directories: BTreeMap<Directory, DirectoryDiagnosis>,
+}
+
+impl DoctorReport {
+    /// Whether any of the directories cannot be used
+    pub fn has_problems(&self) -> bool {
+        self.directories
+            .values()
+            .any(DirectoryDiagnosis::has_problems)
+    }
+
+    /// All of the issues with their directories
+    pub fn issues(&self) -> impl Iterator<Item = (&Directory, &Issue)> {
+        self.directories
+            .iter()
+            .flat_map(|(dir, d)| d.issues.iter().map(move |issue| (dir, issue)))
+    }
+}
+
+/// Diagnosis for each of the [`Scoped`] scopes
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct ScopedDoctorReport {
+    pub user: DoctorReport,
+    pub system: DoctorReport,
+    pub local: DoctorReport,
+}
+
+impl ScopedDoctorReport {
+    /// Whether any of the directories cannot be used
+    pub fn has_problems(&self) -> bool {
+        self.user.has_problems() || self.system.has_problems() || self.local.has_problems()
+    }
+}
+
+/// Health check of the project directories. Explains what [`crate::dir_utils::Filter`] only
+/// filters out, meant for the support bundles
+pub trait Doctor {
+    type Report;
+
+    /// Diagnose directories. Ownership is compared with the uid from the given [`EnvSource`]
+    fn diagnose_with_env(&self, env: &dyn EnvSource) -> Self::Report;
+
+    /// Same as [`Doctor::diagnose_with_env`]. Uses uid of the current user
+    fn diagnose(&self) -> Self::Report {
+        self.diagnose_with_env(&SystemEnv)
+    }
+}
+
+impl Doctor for ProjectDirs {
+    type Report = DoctorReport;
+
+    fn diagnose_with_env(&self, env: &dyn EnvSource) -> DoctorReport {
+        let uid = env.uid();
+
+        DoctorReport {
+            uid,
+            directories: self
+                .0
+                .iter()
+                .map(|(dir, path)| (dir.clone(), DirectoryDiagnosis::new(dir, path, uid)))
+                .collect(),
+        }
+    }
+}
+
+impl Doctor for FullProjectDirs {
+    type Report = DoctorReport;
+
+    fn diagnose_with_env(&self, env: &dyn EnvSource) -> DoctorReport {
+        ProjectDirs::from(self.clone()).diagnose_with_env(env)
+    }
+}
+
+impl Doctor for Scoped {
+    type Report = ScopedDoctorReport;
+
+    fn diagnose_with_env(&self, env: &dyn EnvSource) -> ScopedDoctorReport {
+        ScopedDoctorReport {
+            user: self.user.diagnose_with_env(env),
+            system: self.system.diagnose_with_env(env),
+            local: self.local.diagnose_with_env(env),
+        }
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Source of the process environment used to resolve project directories
+pub trait EnvSource {
+    /// Get env variable. Unset and non-unicode variables are `None`
+    fn var(&self, name: &str) -> Option<String>;
+
+    /// Get home directory of the current user
+    fn home_dir(&self) -> Option<PathBuf>;
+
+    /// Get current working directory
+    fn current_dir(&self) -> std::io::Result<PathBuf>;
+
+    /// Get path of the current executable
+    fn current_exe(&self) -> std::io::Result<PathBuf>;
+
+    /// Get uid of the current user. Always `None` on non-unix systems
+    fn uid(&self) -> Option<u32>;
+
+    /// Get hostname of the current machine
+    fn hostname(&self) -> Option<String>;
+}
+
+/// [`EnvSource`] reading the real process state
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemEnv;
+
+impl EnvSource for SystemEnv {
+    fn var(&self, name: &str) -> Option<String> {
+        std::env::var(name).ok()
+    }
+
+    fn home_dir(&self) -> Option<PathBuf> {
+        crate::dir_utils::home_dir()
+    }
+
+    fn current_dir(&self) -> std::io::Result<PathBuf> {
+        std::env::current_dir()
+    }
+
+    fn current_exe(&self) -> std::io::Result<PathBuf> {
+        std::env::current_exe()
+    }
+
+    fn uid(&self) -> Option<u32> {
+        #[cfg(unix)]
+        {
+            // SAFETY: getuid is always successful and has no side effects
+            Some(unsafe { libc::getuid() })
+        }
+
+        #[cfg(not(unix))]
+        {
+            None
+        }
+    }
+
+    fn hostname(&self) -> Option<String> {
+        #[cfg(unix)]
+        {
+            let mut buf = [0u8; 256];
+            // SAFETY: buffer is valid for buf.len() bytes
+            let result = unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) };
+            if result != 0 {
+                return None;
+            }
+            let len = buf.iter().position(|b| *b == 0).unwrap_or(buf.len());
+            String::from_utf8(buf[..len].to_vec()).ok()
+        }
+
+        #[cfg(not(unix))]
+        {
+            self.var("COMPUTERNAME")
+        }
+    }
+}
+
+/// In-memory [`EnvSource`]. Nothing is read from the process, so it is safe to use in parallel
+/// tests
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MemoryEnv {
+    pub vars: HashMap<String, String>,
+    /// Home directory. Falls back to the `HOME` variable
+    pub home_dir: Option<PathBuf>,
+    pub current_dir: Option<PathBuf>,
+    pub current_exe: Option<PathBuf>,
+    pub uid: Option<u32>,
+    pub hostname: Option<String>,
+}
+
+impl MemoryEnv {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_var(mut self, name: &str, value: &str) -> Self {
+        self.vars.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    pub fn with_home_dir(mut self, home_dir: impl Into<PathBuf>) -> Self {
+        self.home_dir = Some(home_dir.into());
+        self
+    }
+
+    pub fn with_current_dir(mut self, current_dir: impl Into<PathBuf>) -> Self {
+        self.current_dir = Some(current_dir.into());
+        self
+    }
+
+    pub fn with_current_exe(mut self, current_exe: impl Into<PathBuf>) -> Self {
+        self.current_exe = Some(current_exe.into());
+        self
+    }
+
+    pub fn with_uid(mut self, uid: u32) -> Self {
+        self.uid = Some(uid);
+        self
+    }
+
+    pub fn with_hostname(mut self, hostname: &str) -> Self {
+        self.hostname = Some(hostname.to_string());
+        self
+    }
+}
+
+fn not_set(what: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::NotFound, format!("{what} is not set"))
+}
+
+impl EnvSource for MemoryEnv {
+    fn var(&self, name: &str) -> Option<String> {
+        self.vars.get(name).cloned()
+    }
+
+    fn home_dir(&self) -> Option<PathBuf> {
+        self.home_dir.clone().or_else(|| {
+            self.var("HOME")
+                .filter(|home| !home.is_empty())
+                .map(PathBuf::from)
+        })
+    }
+
+    fn current_dir(&self) -> std::io::Result<PathBuf> {
+        self.current_dir
+            .clone()
+            .ok_or_else(|| not_set("current dir"))
+    }
+
+    fn current_exe(&self) -> std::io::Result<PathBuf> {
+        self.current_exe
+            .clone()
+            .ok_or_else(|| not_set("current exe"))
+    }
+
+    fn uid(&self) -> Option<u32> {
+        self.uid
+    }
+
+    fn hostname(&self) -> Option<String> {
+        self.hostname.clone()
+    }
+}
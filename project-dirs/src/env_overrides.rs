@@ -0,0 +1,96 @@
+use std::path::PathBuf;
+
+use crate::env::EnvSource;
+use crate::{BUILTIN_DIRECTORIES, Directory, Project, ProjectDirs};
+
+/// Uppercase the name and replace everything except ASCII letters and digits with `_`
+fn variable_segment(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn directory_segment(dir: &Directory) -> String {
+    match dir {
+        Directory::Bin => "BIN".to_string(),
+        Directory::Cache => "CACHE".to_string(),
+        Directory::Config => "CONFIG".to_string(),
+        Directory::Data => "DATA".to_string(),
+        Directory::Include => "INCLUDE".to_string(),
+        Directory::Lib => "LIB".to_string(),
+        Directory::Log => "LOG".to_string(),
+        Directory::ProjectRoot => "PROJECT_ROOT".to_string(),
+        Directory::Runtime => "RUNTIME".to_string(),
+        Directory::State => "STATE".to_string(),
+        Directory::Custom(name) => variable_segment(name),
+    }
+}
+
+/// Per-directory overrides read from the env variables, e.g. `MYAPP_DATA_DIR=/srv/big-disk`.
+/// Variable name is `<PREFIX>_<DIRECTORY>_DIR`. See [`Project::with_env_overrides`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct EnvOverrides {
+    /// Prefix of the variables. Defaults to the uppercased [`Project::application_name_unix`]
+    /// (e.g. `MY_APP` for `my-app`)
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub prefix: Option<String>,
+}
+
+impl EnvOverrides {
+    /// Overrides with the prefix derived from the application name
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Overrides with the custom prefix, e.g. `MYAPP`
+    pub fn with_prefix(prefix: &str) -> Self {
+        Self {
+            prefix: Some(prefix.to_string()),
+        }
+    }
+
+    /// Prefix used for the given project
+    pub fn prefix(&self, project: &Project) -> String {
+        match &self.prefix {
+            Some(prefix) => prefix.clone(),
+            None => variable_segment(&project.application_name_unix()),
+        }
+    }
+
+    /// Name of the variable overriding the directory, e.g. `MY_APP_DATA_DIR`
+    pub fn variable_name(&self, project: &Project, dir: &Directory) -> String {
+        format!("{}_{}_DIR", self.prefix(project), directory_segment(dir))
+    }
+
+    /// Replace (or add) directories set in the env. Empty values and relative paths are ignored
+    pub fn apply_with_env(
+        &self,
+        project: &Project,
+        mut dirs: ProjectDirs,
+        env: &dyn EnvSource,
+    ) -> ProjectDirs {
+        let custom = project.custom_directories().iter().map(|c| c.directory());
+
+        // Custom ones come from the project
+        for dir in BUILTIN_DIRECTORIES.into_iter().chain(custom) {
+            let path = env
+                .var(&self.variable_name(project, &dir))
+                .filter(|value| !value.is_empty())
+                .map(PathBuf::from)
+                .filter(|path| path.is_absolute());
+
+            if let Some(path) = path {
+                dirs.0.insert(dir, path);
+            }
+        }
+        dirs
+    }
+}
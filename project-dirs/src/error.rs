@@ -0,0 +1,90 @@
+use std::path::PathBuf;
+
+use crate::Directory;
+
+/// Error of the project directories resolution. Strategies that cannot provide a directory on
+/// the given platform simply skip it; errors are returned only when resolution fails.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Home directory of the current user cannot be resolved
+    #[error("Home directory cannot be resolved")]
+    MissingHomeDir,
+
+    /// Current working directory cannot be read
+    #[error("Current working directory cannot be read")]
+    UnreadableCurrentDir(#[source] std::io::Error),
+
+    /// Path of the current executable cannot be read
+    #[error("Current executable cannot be read")]
+    UnreadableCurrentExe(#[source] std::io::Error),
+
+    /// Env variable is set, but its value cannot be used
+    #[error("Invalid value of the {name} variable: {reason}")]
+    InvalidEnvValue { name: String, reason: String },
+
+    /// Strategy cannot be used on the current platform (e.g. `wasm32`)
+    #[error("Platform {0} is not supported")]
+    UnsupportedPlatform(String),
+
+    /// Application is not running in the Flatpak or Snap sandbox
+    #[error("Sandbox (Flatpak or Snap) is not detected")]
+    SandboxNotDetected,
+
+    /// Strategy did not provide all of the required directories
+    #[error("Missing directories: {0:?}")]
+    MissingDirectories(Vec<Directory>),
+
+    /// Component of the [`crate::ProjectId`] is missing or invalid
+    #[error("Invalid {component} {value:?}: {reason}")]
+    InvalidProjectId {
+        component: String,
+        value: String,
+        reason: String,
+    },
+
+    /// Name of the [`crate::CustomDirectory`] is invalid, or the custom directory is not declared
+    #[error("Invalid custom directory {name:?}: {reason}")]
+    InvalidCustomDirectory { name: String, reason: String },
+
+    /// Strategy with the given name is not registered
+    #[error("Unknown strategy: {0}")]
+    UnknownStrategy(String),
+
+    /// There is no chain of registered migrations between the versions
+    #[error("No migration path from version {from} to {to}")]
+    NoMigrationPath { from: String, to: String },
+
+    /// Lock is held by another process. PID is known if the owner is alive
+    #[error("Lock {path:?} is held by another instance{}", .pid.map(|pid| format!(" (pid {pid})")).unwrap_or_default())]
+    Locked { path: PathBuf, pid: Option<u32> },
+
+    /// Path does not fit in the unix socket address, even after the fallback
+    #[error("Socket path {path:?} is longer than {max} bytes")]
+    SocketPathTooLong { path: PathBuf, max: usize },
+
+    /// Directory in the shared location (e.g. `/tmp`) is not a private directory of the current
+    /// user, e.g. it is a symlink or it is owned by another user
+    #[error("Directory {path:?} is unsafe to use: {reason}")]
+    UnsafeDirectory { path: PathBuf, reason: String },
+
+    /// Target of the migration already exists, so the data would be overwritten or orphaned
+    #[error("Cannot migrate to {path:?}: it already exists")]
+    MigrationConflict { path: PathBuf },
+
+    /// Filesystem operation failed
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    /// Any other error, e.g. returned by the application-defined strategy
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl Error {
+    pub fn custom(message: impl Into<String>) -> Self {
+        Self::Custom(message.into())
+    }
+}
+
+/// Result with the [`Error`]
+pub type Result<T> = std::result::Result<T, Error>;
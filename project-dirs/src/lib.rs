@@ -1,15 +1,49 @@
+use std::path::{Component, Path, PathBuf};
+
+mod env_overrides;
 mod proj_dirs;
+mod project_id;
 mod project_triplet;
 
 /// Utility functions and traits for project directories
 pub mod dir_utils;
 
+/// Health check of the project directories (ownership, modes, filesystems, free space)
+pub mod doctor;
+
+/// Error of the project directories resolution
+mod error;
+
+/// Single-instance lock with the PID file
+pub mod lock;
+
+/// Unix socket paths fitting in the `sun_path`
+pub mod socket;
+
+/// Per-process temp directories in the runtime (or cache) directory
+pub mod temp;
+
+/// Migrations between application versions and between directory layouts
+pub mod migration;
+
+/// Sources of the environment (variables, home, cwd etc.) used by strategies
+pub mod env;
+
 /// Ways of retrieving project directories
 pub mod strategy;
 
-pub use proj_dirs::{FullProjectDirs, MissingError, ProjectDirs};
+use env::{EnvSource, SystemEnv};
+pub use env_overrides::EnvOverrides;
+pub use error::{Error, Result};
+pub use proj_dirs::{
+    BUILTIN_DIRECTORIES, FullProjectDirs, INSTANCE_DIRECTORIES, MissingError, ProjectDirs,
+    VERSIONED_DIRECTORIES, version_dir_name,
+};
+pub use project_id::{ProjectId, ProjectIdBuilder};
+pub use project_triplet::{NameCollision, NamingMode, find_name_collisions, transliterate};
 
 /// Definition of the project essentials. Allows to retrive project directories
+#[derive(Debug, Clone)]
 pub struct Project {
     /// NOTE: You should rather use qualifier_value
     _orig_qualifier: String,
@@ -21,10 +55,16 @@ pub struct Project {
     qualifier_value: String,
     organization_name: String,
     application_name: String,
+
+    custom_directories: Vec<CustomDirectory>,
+    version: Option<String>,
+    instance: Option<String>,
+    env_overrides: Option<EnvOverrides>,
+    naming_mode: NamingMode,
 }
 
 /// Purpose of directory existence. Ex. Bin, Config, Cache etc.
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, PartialOrd, Ord)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, PartialOrd, Ord)]
 #[cfg_attr(feature = "strum", derive(strum::Display, strum::EnumString))]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
@@ -50,6 +90,97 @@ pub enum Directory {
     Runtime,
     /// Non-essential data files that should persist between sessions. E.g. logs, history
     State,
+    /// Application-defined directory, e.g. `plugins`. See [`CustomDirectory`]
+    #[cfg_attr(feature = "strum", strum(default, to_string = "{0}"))]
+    #[cfg_attr(feature = "serde", serde(untagged))]
+    Custom(String),
+}
+
+impl Directory {
+    /// Name used in the manifests and serialized form, e.g. `project-root` or `plugins`
+    pub fn name(&self) -> &str {
+        match self {
+            Directory::Bin => "bin",
+            Directory::Cache => "cache",
+            Directory::Config => "config",
+            Directory::Data => "data",
+            Directory::Include => "include",
+            Directory::Lib => "lib",
+            Directory::Log => "log",
+            Directory::ProjectRoot => "project-root",
+            Directory::Runtime => "runtime",
+            Directory::State => "state",
+            Directory::Custom(name) => name,
+        }
+    }
+}
+
+/// Definition of the application-defined directory ([`Directory::Custom`]). It is derived from
+/// the other directory, e.g. `plugins` may be a `plugins` subdir of the [`Directory::Data`].
+/// Name consists of lowercase ASCII letters, digits and `-` (starting with a letter) and cannot
+/// be a name of the built-in directory (e.g. `data`). Subpath must be relative and cannot leave
+/// the base directory (no `..`)
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct CustomDirectory {
+    /// Name of the directory type. Used as [`Directory::Custom`] value
+    pub name: String,
+    /// Directory type this one is derived from
+    pub base: Directory,
+    /// Path relative to the base directory
+    pub subpath: PathBuf,
+}
+
+impl CustomDirectory {
+    /// Returns [`Error::InvalidCustomDirectory`] when the name or subpath is invalid
+    pub fn new(name: &str, base: Directory, subpath: impl Into<PathBuf>) -> Result<Self> {
+        let custom = Self {
+            name: name.to_string(),
+            base,
+            subpath: subpath.into(),
+        };
+        custom.validate()?;
+        Ok(custom)
+    }
+
+    /// Check the name and subpath (e.g. of the deserialized definition)
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |reason: &str| {
+            Err(Error::InvalidCustomDirectory {
+                name: self.name.clone(),
+                reason: reason.to_string(),
+            })
+        };
+
+        if BUILTIN_DIRECTORIES
+            .iter()
+            .any(|dir| dir.name() == self.name)
+        {
+            invalid("name of the built-in directory")
+        } else if !self.name.starts_with(|c: char| c.is_ascii_lowercase()) {
+            invalid("must start with a lowercase ASCII letter")
+        } else if !self
+            .name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        {
+            invalid("only lowercase ASCII letters, digits and '-' are allowed")
+        } else if !self
+            .subpath
+            .components()
+            .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+        {
+            invalid("subpath must be relative and stay inside the base directory")
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Get the [`Directory::Custom`] for this definition
+    pub fn directory(&self) -> Directory {
+        Directory::Custom(self.name.clone())
+    }
 }
 
 /// Project directories gathered by scope: user, system and local (pwd)
@@ -61,6 +192,78 @@ pub struct Scoped {
     pub local: ProjectDirs,
 }
 
+/// Scope of the [`Scoped`] project directories
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "strum", derive(strum::Display, strum::EnumString))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum Scope {
+    /// Current user
+    User,
+    /// Whole system
+    System,
+    /// Current working directory
+    Local,
+}
+
+impl Scope {
+    /// Default precedence: local overrides user, user overrides system
+    pub const DEFAULT_PRECEDENCE: [Scope; 3] = [Scope::Local, Scope::User, Scope::System];
+}
+
+/// Files found by [`Scoped::resolve_file`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedFile {
+    /// Existing candidates in the order of precedence (most important first)
+    pub candidates: Vec<(Scope, PathBuf)>,
+}
+
+impl ResolvedFile {
+    /// The single file that should be used (the most important one)
+    pub fn effective(&self) -> Option<&PathBuf> {
+        self.candidates.first().map(|(_, path)| path)
+    }
+}
+
+impl Scoped {
+    /// Get project directories for the given scope
+    pub fn get(&self, scope: Scope) -> &ProjectDirs {
+        match scope {
+            Scope::User => &self.user,
+            Scope::System => &self.system,
+            Scope::Local => &self.local,
+        }
+    }
+
+    /// Find the relative file (e.g. `config.toml`) within the given directory type of each
+    /// scope. Uses [`Scope::DEFAULT_PRECEDENCE`]
+    pub fn resolve_file(&self, name: impl AsRef<Path>, dir: Directory) -> ResolvedFile {
+        self.resolve_file_with_precedence(name, dir, &Scope::DEFAULT_PRECEDENCE)
+    }
+
+    /// Same as [`Scoped::resolve_file`], but with custom precedence (most important first).
+    /// Scopes missing in the precedence are skipped
+    pub fn resolve_file_with_precedence(
+        &self,
+        name: impl AsRef<Path>,
+        dir: Directory,
+        precedence: &[Scope],
+    ) -> ResolvedFile {
+        ResolvedFile {
+            candidates: precedence
+                .iter()
+                .filter_map(|scope| {
+                    self.get(*scope)
+                        .get(&dir)
+                        .map(|path| (*scope, path.join(name.as_ref())))
+                })
+                .filter(|(_, path)| path.exists())
+                .collect(),
+        }
+    }
+}
+
 impl Project {
     pub fn new(qualifier: &str, organization: &str, application: &str) -> Self {
         Self {
@@ -70,27 +273,106 @@ impl Project {
             qualifier_value: project_triplet::qualifier_cleanup(qualifier),
             organization_name: project_triplet::name_cleanup(organization),
             application_name: project_triplet::name_cleanup(application),
+            custom_directories: Vec::new(),
+            version: None,
+            instance: None,
+            env_overrides: None,
+            naming_mode: NamingMode::default(),
         }
     }
 
+    /// Change handling of the non-ASCII characters in the organization and application names
+    pub fn with_naming_mode(mut self, naming_mode: NamingMode) -> Self {
+        self.naming_mode = naming_mode;
+        self.organization_name =
+            project_triplet::name_cleanup_with_mode(&self._orig_organization, naming_mode);
+        self.application_name =
+            project_triplet::name_cleanup_with_mode(&self._orig_application, naming_mode);
+        self
+    }
+
+    /// Get naming mode used for the organization and application names
+    pub fn naming_mode(&self) -> NamingMode {
+        self.naming_mode
+    }
+
+    /// Check whether other project with different application name uses the same directory
+    /// name on the current system (e.g. `Über App` and `uber-app` with transliteration)
+    pub fn collides_with(&self, other: &Project) -> bool {
+        self._orig_application != other._orig_application
+            && self.application_name == other.application_name
+    }
+
+    /// Use versioned layout: [`VERSIONED_DIRECTORIES`] in [`Project::project_dirs`] get the
+    /// version subdirectory, e.g. `data/<app>/v2`. See [`migration`] for moving data between
+    /// versions
+    pub fn with_version(mut self, version: &str) -> Self {
+        self.version = Some(version.to_string());
+        self
+    }
+
+    /// Get application version used for the versioned layout
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    /// Use named instance (e.g. `app@blue`): [`INSTANCE_DIRECTORIES`] in
+    /// [`Project::project_dirs`] get the instance subdirectory, e.g. `data/<app>/blue`. Name is
+    /// cleaned up like the application name; instance empty after the cleanup is ignored
+    pub fn with_instance(mut self, instance: &str) -> Self {
+        let instance = project_triplet::name_cleanup_with_mode(instance, self.naming_mode);
+        self.instance = (!instance.trim().is_empty()).then_some(instance);
+        self
+    }
+
+    /// Get name of the instance (after the cleanup)
+    pub fn instance(&self) -> Option<&str> {
+        self.instance.as_deref()
+    }
+
+    /// Allow to override single directories with the env variables, e.g.
+    /// `MY_APP_DATA_DIR=/srv/big-disk` (see [`EnvOverrides`]). Overrides are applied on top of
+    /// the strategy (after the instance and version), custom directories are derived from them
+    pub fn with_env_overrides(mut self, env_overrides: EnvOverrides) -> Self {
+        self.env_overrides = Some(env_overrides);
+        self
+    }
+
+    /// Get env overrides (if enabled)
+    pub fn env_overrides(&self) -> Option<&EnvOverrides> {
+        self.env_overrides.as_ref()
+    }
+
+    /// Register application-defined directory type. It is added to the [`Project::project_dirs`]
+    /// scopes that resolve its base directory
+    pub fn with_custom_directory(mut self, custom_directory: CustomDirectory) -> Self {
+        self.custom_directories.push(custom_directory);
+        self
+    }
+
+    /// Get registered application-defined directory types
+    pub fn custom_directories(&self) -> &[CustomDirectory] {
+        &self.custom_directories
+    }
+
     /// Get application name for UNIX-like systems (excluding mac)
     pub fn application_name_unix(&self) -> String {
-        project_triplet::unix_name_cleanup(&self._orig_application)
+        project_triplet::unix_name_cleanup_with_mode(&self._orig_application, self.naming_mode)
     }
 
     /// Get application name for Windows systems
     pub fn application_name_windows(&self) -> String {
-        project_triplet::windows_name_cleanup(&self._orig_application)
+        project_triplet::windows_name_cleanup_with_mode(&self._orig_application, self.naming_mode)
     }
 
     /// Get application name for macOS
     pub fn application_name_macos(&self) -> String {
-        project_triplet::unix_name_cleanup(&self._orig_application)
+        project_triplet::unix_name_cleanup_with_mode(&self._orig_application, self.naming_mode)
     }
 
     /// Get organization name for Windows
     pub fn organization_name_windows(&self) -> String {
-        project_triplet::windows_name_cleanup(&self._orig_organization)
+        project_triplet::windows_name_cleanup_with_mode(&self._orig_organization, self.naming_mode)
     }
 
     /// Get organization_name calculated for the current system
@@ -108,53 +390,210 @@ impl Project {
         &self.qualifier_value
     }
 
+    /// Portable layout (see [`strategy::portable`]) replaces the user scope when enabled
+    #[cfg(any(target_family = "unix", target_family = "windows"))]
+    fn portable_or(&self, env: &dyn EnvSource, user: impl FnOnce() -> ProjectDirs) -> ProjectDirs {
+        use crate::strategy::portable::Portable;
+
+        if self.portable_exe_dir_with_env(env).is_none() {
+            return user();
+        }
+        self.portable_with_env(env)
+            .map(Into::into)
+            .unwrap_or(ProjectDirs::empty())
+    }
+
     #[cfg(all(target_family = "unix", not(target_os = "macos")))]
-    fn unix_project_dirs(&self) -> Scoped {
-        use crate::strategy::fhs::Fhs;
+    fn unix_project_dirs(&self, env: &dyn EnvSource) -> Scoped {
+        use crate::strategy::sandbox::{Sandbox, SandboxEnv};
         use crate::strategy::unix::Unix;
         use crate::strategy::xdg::{Xdg, XdgEnv};
 
+        #[cfg(target_os = "freebsd")]
+        let system = {
+            use crate::strategy::bsd::Hier;
+            self.hier().into()
+        };
+
+        #[cfg(not(target_os = "freebsd"))]
+        let system = {
+            use crate::strategy::fhs::Fhs;
+            self.fhs().into()
+        };
+
+        // Sandbox detected only from the system variables (e.g. snap service with `SNAP_DATA`)
+        // has no user directories, so XDG is used as for the unsandboxed application
+        let sandbox_env = SandboxEnv::from_source(env);
+        let user = match sandbox_env.sandbox() {
+            Some(_) => self
+                .sandbox_user_with_env(sandbox_env)
+                .or_else(|_| self.xdg_with_env(XdgEnv::from_source(env))),
+            None => self.xdg_with_env(XdgEnv::from_source(env)),
+        };
+
         Scoped {
-            user: self
-                .xdg_with_env(XdgEnv::new_system())
-                .map(|x| x.into())
+            user: self.portable_or(env, || user.map(Into::into).unwrap_or(ProjectDirs::empty())),
+            system,
+            local: self
+                .unix_pwd_with_env(env)
+                .map(Into::into)
                 .unwrap_or(ProjectDirs::empty()),
-            system: self.fhs().into(),
+        }
+    }
+
+    #[cfg(target_os = "macos")]
+    fn macos_project_dirs(&self, env: &dyn EnvSource) -> Scoped {
+        use crate::strategy::macos::{Macos, MacosEnv};
+        use crate::strategy::unix::Unix;
+
+        Scoped {
+            user: self.portable_or(env, || {
+                self.macos_user_with_env(MacosEnv::from_source(env))
+                    .map(Into::into)
+                    .unwrap_or(ProjectDirs::empty())
+            }),
+            system: self.macos_system().into(),
             local: self
-                .unix_pwd()
+                .unix_pwd_with_env(env)
                 .map(Into::into)
                 .unwrap_or(ProjectDirs::empty()),
         }
     }
 
     #[cfg(target_os = "windows")]
-    fn windows_project_dirs(&self) -> Scoped {
+    fn windows_project_dirs(
+        &self,
+        env: &dyn EnvSource,
+        windows_env: crate::strategy::windows::WindowsEnv,
+    ) -> Scoped {
         use crate::strategy::unix::Unix;
+        use crate::strategy::windows::Windows;
 
-        use crate::strategy::windows::{Windows, WindowsEnv};
-        let windows_env = WindowsEnv::new_system();
         Scoped {
-            user: self.windows_user_with_env(windows_env.clone()),
+            user: self.portable_or(env, || self.windows_user_with_env(windows_env.clone())),
             system: self.windows_system_with_env(windows_env),
             local: self
-                .unix_pwd()
+                .unix_pwd_with_env(env)
                 .map(Into::into)
                 .unwrap_or(ProjectDirs::empty()),
         }
     }
 
+    /// Get project directories for the current system. Inside Flatpak or Snap the user scope uses
+    /// [`strategy::sandbox::Sandbox`] directories. In the portable mode (marker file next to the
+    /// executable) it uses [`strategy::portable::Portable`] directories
     pub fn project_dirs(&self) -> Scoped {
-        #[cfg(all(target_family = "unix", not(target_os = "macos")))]
+        #[cfg(target_family = "windows")]
         {
-            self.unix_project_dirs()
+            use crate::strategy::windows::WindowsEnv;
+            self.finalize_scoped(
+                self.windows_project_dirs(&SystemEnv, WindowsEnv::new_system()),
+                &SystemEnv,
+            )
         }
-        #[cfg(all(target_family = "unix", target_os = "macos"))]
+
+        #[cfg(not(target_family = "windows"))]
         {
-            todo!()
+            self.project_dirs_with_env(&SystemEnv)
         }
+    }
+
+    /// Get project directories for the current system using the given [`EnvSource`]. On
+    /// Windows known folders are read from the env variables (see
+    /// [`strategy::windows::WindowsEnv::from_source`])
+    pub fn project_dirs_with_env(&self, env: &dyn EnvSource) -> Scoped {
+        self.finalize_scoped(self.strategy_dirs_with_env(env), env)
+    }
+
+    /// Directories of the default strategies of the current system, before
+    /// [`Project::finalize_project_dirs`] (instance, version, env overrides, custom directories)
+    fn strategy_dirs_with_env(&self, env: &dyn EnvSource) -> Scoped {
+        #[cfg(all(target_family = "unix", not(target_os = "macos")))]
+        let scoped = self.unix_project_dirs(env);
+
+        #[cfg(all(target_family = "unix", target_os = "macos"))]
+        let scoped = self.macos_project_dirs(env);
+
         #[cfg(target_family = "windows")]
-        {
-            self.windows_project_dirs()
+        let scoped =
+            self.windows_project_dirs(env, crate::strategy::windows::WindowsEnv::from_source(env));
+
+        #[cfg(not(any(target_family = "unix", target_family = "windows")))]
+        let scoped = {
+            let _ = env;
+            Scoped {
+                user: ProjectDirs::empty(),
+                system: ProjectDirs::empty(),
+                local: ProjectDirs::empty(),
+            }
+        };
+
+        scoped
+    }
+
+    /// Same as [`Project::project_dirs_with_env`], but returns [`Error::UnsupportedPlatform`]
+    /// instead of empty directories on platforms without default strategies
+    pub fn try_project_dirs_with_env(&self, env: &dyn EnvSource) -> Result<Scoped> {
+        Ok(self.finalize_scoped(self.try_strategy_dirs_with_env(env)?, env))
+    }
+
+    /// Same as [`Project::try_project_dirs_with_env`], but not finalized. Used by the
+    /// [`strategy::StrategyRegistry`], as the strategies are finalized by the caller
+    pub(crate) fn try_strategy_dirs_with_env(&self, env: &dyn EnvSource) -> Result<Scoped> {
+        if cfg!(any(target_family = "unix", target_family = "windows")) {
+            Ok(self.strategy_dirs_with_env(env))
+        } else {
+            Err(Error::UnsupportedPlatform(std::env::consts::OS.to_string()))
+        }
+    }
+
+    /// Apply project version and custom directories
+    fn finalize_scoped(&self, scoped: Scoped, env: &dyn EnvSource) -> Scoped {
+        Scoped {
+            user: self.finalize_project_dirs_with_env(scoped.user, env),
+            system: self.finalize_project_dirs_with_env(scoped.system, env),
+            local: self.finalize_project_dirs_with_env(scoped.local, env),
         }
     }
+
+    /// Apply project instance and version (if any), env overrides and custom directories. Custom
+    /// directories are derived from the versioned ones. Each instance has own version
+    pub fn finalize_project_dirs(&self, dirs: ProjectDirs) -> ProjectDirs {
+        self.finalize_project_dirs_with_env(dirs, &SystemEnv)
+    }
+
+    /// Same as [`Project::finalize_project_dirs`], but env overrides are read from the given
+    /// [`EnvSource`]
+    pub fn finalize_project_dirs_with_env(
+        &self,
+        dirs: ProjectDirs,
+        env: &dyn EnvSource,
+    ) -> ProjectDirs {
+        self.finalize_with_instance(dirs, self.instance.as_deref(), env)
+    }
+
+    /// Same as [`Project::finalize_project_dirs_with_env`] with the given instance segment (e.g.
+    /// systemd `%i` specifier)
+    pub(crate) fn finalize_with_instance(
+        &self,
+        dirs: ProjectDirs,
+        instance: Option<&str>,
+        env: &dyn EnvSource,
+    ) -> ProjectDirs {
+        let dirs = match instance {
+            Some(instance) => dirs.with_instance(instance),
+            None => dirs,
+        };
+        let dirs = match &self.version {
+            Some(version) => dirs.with_version(version),
+            None => dirs,
+        };
+        let overridden = |dirs: ProjectDirs| match &self.env_overrides {
+            Some(overrides) => overrides.apply_with_env(self, dirs, env),
+            None => dirs,
+        };
+
+        // Custom directories are derived from the overridden ones, but may be overridden as well
+        overridden(overridden(dirs).with_custom_directories(&self.custom_directories))
+    }
 }
@@ -0,0 +1,234 @@
+use std::fs::File;
+use std::io::{Read, Seek, Write};
+use std::path::{Path, PathBuf};
+
+use crate::dir_utils::{ProvisionPolicy, create_dir_with_mode};
+use crate::{Directory, Error, ProjectDirs, Result};
+
+/// Name of the lock (and PID) file of the [`ProjectDirs::instance_lock`]
+pub const LOCK_FILE: &str = "instance.lock";
+
+/// Exclusive lock of the single application instance. The lock file contains PID of the owner.
+/// It is released on drop (the file is removed on unix). Uses `flock` on unix and exclusive file
+/// access on Windows, so the lock is released by the OS when the process dies
+#[derive(Debug)]
+pub struct InstanceLock {
+    file: File,
+    path: PathBuf,
+    stale_pid: Option<u32>,
+}
+
+impl InstanceLock {
+    /// Acquire lock of the given file without waiting. Returns [`Error::Locked`] when it is held
+    /// by another process
+    pub fn acquire(path: &Path) -> Result<Self> {
+        // The file may be removed by the previous owner between open and lock, so the lock is
+        // valid only if the path still points to the locked file
+        loop {
+            let mut file = match lock_file(path) {
+                Ok(file) => file,
+                Err(e) if is_contended(&e) => {
+                    return Err(Error::Locked {
+                        path: path.to_path_buf(),
+                        pid: read_pid(path).filter(|pid| process_alive(*pid)),
+                    });
+                }
+                Err(e) => return Err(e.into()),
+            };
+            if !same_file(&file, path) {
+                continue;
+            }
+
+            let mut content = String::new();
+            file.read_to_string(&mut content)?;
+            let own_pid = std::process::id();
+            // Lock would not be acquired if the previous owner was alive (unless the PID was
+            // reused)
+            let stale_pid =
+                parse_pid(&content).filter(|pid| *pid != own_pid && !process_alive(*pid));
+
+            file.set_len(0)?;
+            file.rewind()?;
+            writeln!(file, "{own_pid}")?;
+            file.sync_all()?;
+
+            return Ok(Self {
+                file,
+                path: path.to_path_buf(),
+                stale_pid,
+            });
+        }
+    }
+
+    /// Path of the lock file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// PID of the dead process that did not release the lock (e.g. after a crash). Use it to
+    /// clean up or recover after the previous instance
+    pub fn stale_pid(&self) -> Option<u32> {
+        self.stale_pid
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        // Truncated, so the next owner does not report it as stale
+        let _ = self.file.set_len(0);
+        // Removed while still locked, so the other processes detect it with `same_file`. Opened
+        // files cannot be removed on Windows
+        #[cfg(unix)]
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// PID written in the lock file, if the owner process is alive
+pub fn locked_pid(path: &Path) -> Option<u32> {
+    read_pid(path).filter(|pid| process_alive(*pid))
+}
+
+fn read_pid(path: &Path) -> Option<u32> {
+    parse_pid(&std::fs::read_to_string(path).ok()?)
+}
+
+fn parse_pid(content: &str) -> Option<u32> {
+    content.trim().parse().ok().filter(|pid| *pid != 0)
+}
+
+#[cfg(unix)]
+fn lock_file(path: &Path) -> std::io::Result<File> {
+    use std::os::fd::AsRawFd;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .mode(0o644)
+        .open(path)?;
+
+    // SAFETY: file descriptor is valid as long as the file is open
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(file)
+}
+
+#[cfg(windows)]
+fn lock_file(path: &Path) -> std::io::Result<File> {
+    use std::os::windows::fs::OpenOptionsExt;
+
+    // FILE_SHARE_READ. Others can read the PID, but cannot open the file for writing
+    std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .share_mode(0x1)
+        .open(path)
+}
+
+#[cfg(not(any(unix, windows)))]
+fn lock_file(_path: &Path) -> std::io::Result<File> {
+    Err(std::io::Error::from(std::io::ErrorKind::Unsupported))
+}
+
+fn is_contended(error: &std::io::Error) -> bool {
+    #[cfg(windows)]
+    {
+        // ERROR_SHARING_VIOLATION
+        error.raw_os_error() == Some(32)
+    }
+
+    #[cfg(not(windows))]
+    {
+        error.kind() == std::io::ErrorKind::WouldBlock
+    }
+}
+
+#[cfg(unix)]
+fn same_file(file: &File, path: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    match (file.metadata(), std::fs::metadata(path)) {
+        (Ok(locked), Ok(current)) => locked.dev() == current.dev() && locked.ino() == current.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_file(_file: &File, _path: &Path) -> bool {
+    // Opened file cannot be removed by the other process
+    true
+}
+
+/// Whether the process exists. Always `true` on systems other than unix and Windows, as the
+/// liveness cannot be checked there
+#[cfg(unix)]
+pub(crate) fn process_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // SAFETY: signal 0 only checks if the signal could be sent
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return true;
+    }
+    // Process exists, but belongs to the other user
+    std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(windows)]
+pub(crate) fn process_alive(pid: u32) -> bool {
+    use windows_sys::Win32::Foundation::{
+        CloseHandle, ERROR_ACCESS_DENIED, GetLastError, STILL_ACTIVE,
+    };
+    use windows_sys::Win32::System::Threading::{
+        GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    // SAFETY: handle is checked and closed, exit code is written to the local variable
+    unsafe {
+        let handle = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid);
+        if handle.is_null() {
+            // Process exists, but cannot be queried (e.g. protected system process)
+            return GetLastError() == ERROR_ACCESS_DENIED;
+        }
+
+        let mut exit_code = 0;
+        let alive = GetExitCodeProcess(handle, &mut exit_code) == 0
+            || exit_code == STILL_ACTIVE as u32;
+        CloseHandle(handle);
+        alive
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn process_alive(_pid: u32) -> bool {
+    true
+}
+
+impl ProjectDirs {
+    /// Directory of the lock and PID files: [`Directory::Runtime`] or [`Directory::State`] when
+    /// the runtime is not available (e.g. `XDG_RUNTIME_DIR` is unset)
+    pub fn lock_dir(&self) -> Option<&PathBuf> {
+        self.runtime_or(Directory::State).map(|(_, path)| path)
+    }
+
+    /// Acquire [`InstanceLock`] ([`LOCK_FILE`] in the [`ProjectDirs::lock_dir`]). Creates the
+    /// directory with the default [`ProvisionPolicy`] mode when missing
+    pub fn instance_lock(&self) -> Result<InstanceLock> {
+        let (kind, dir) = self
+            .runtime_or(Directory::State)
+            .ok_or(Error::MissingDirectories(vec![
+                Directory::Runtime,
+                Directory::State,
+            ]))?;
+
+        if !dir.is_dir() {
+            create_dir_with_mode(dir, ProvisionPolicy::default().mode(&kind))?;
+        }
+        InstanceLock::acquire(&dir.join(LOCK_FILE))
+    }
+}
@@ -0,0 +1,580 @@
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+
+use crate::{Directory, Error, Project, ProjectDirs, Result, VERSIONED_DIRECTORIES};
+
+/// Name of the file (in the unversioned data dir) that keeps the version of the on-disk data
+pub const VERSION_STAMP: &str = ".version";
+
+/// Version of the data written before the application started to use versions, i.e. directly
+/// in the unversioned directories. Register migration from it (e.g.
+/// `registry.register_move(UNVERSIONED, "1")`) to move the data of the older installations
+pub const UNVERSIONED: &str = "unversioned";
+
+/// Single migration between two application versions. Both directories are derived from the
+/// same unversioned base with [`ProjectDirs::with_version`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct MigrationStep {
+    pub from_version: String,
+    pub to_version: String,
+    /// Versioned directories of the previous version
+    pub from: ProjectDirs,
+    /// Versioned directories of the next version
+    pub to: ProjectDirs,
+}
+
+/// Migrations required to reach the target version. Returned by the
+/// [`MigrationRegistry::plan`] (dry-run) and [`MigrationRegistry::migrate`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct MigrationPlan {
+    /// Version read from the stamp. `None` for fresh installations
+    pub current_version: Option<String>,
+    pub target_version: String,
+    /// Path of the version stamp file
+    pub stamp: PathBuf,
+    /// Migrations in order of execution
+    pub steps: Vec<MigrationStep>,
+}
+
+impl MigrationPlan {
+    /// Whether there is nothing to migrate
+    pub fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+type MigrationFn = Box<dyn Fn(&MigrationStep) -> Result<()> + Send + Sync>;
+
+/// Migrations between application versions, e.g. `1` -> `2` -> `3`. Each migration moves or
+/// transforms the [`VERSIONED_DIRECTORIES`] of the previous version into the next one
+#[derive(Default)]
+pub struct MigrationRegistry {
+    migrations: HashMap<String, Vec<(String, MigrationFn)>>,
+}
+
+impl MigrationRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register migration from one version to another
+    pub fn register(
+        &mut self,
+        from_version: &str,
+        to_version: &str,
+        migration: impl Fn(&MigrationStep) -> Result<()> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.migrations
+            .entry(from_version.to_string())
+            .or_default()
+            .push((to_version.to_string(), Box::new(migration)));
+        self
+    }
+
+    /// Register migration that moves (renames) each of the previous version directories to
+    /// the next version. See [`move_dirs`]
+    pub fn register_move(&mut self, from_version: &str, to_version: &str) -> &mut Self {
+        self.register(from_version, to_version, move_dirs)
+    }
+
+    /// Read version of the on-disk data. `base` are unversioned directories (e.g. from the
+    /// [`crate::Project`] without version). Without the stamp it is [`UNVERSIONED`] if the
+    /// unversioned directories contain data (see [`unversioned_entries`]), `None` for fresh
+    /// installations
+    pub fn current_version(base: &ProjectDirs) -> Result<Option<String>> {
+        match std::fs::read_to_string(stamp_path(base)?) {
+            Ok(content) => return Ok(Some(content.trim().to_string()).filter(|v| !v.is_empty())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+            Err(e) => return Err(e.into()),
+        }
+
+        for dir in VERSIONED_DIRECTORIES {
+            if let Some(path) = base.get(&dir)
+                && !unversioned_entries(base, path)?.is_empty()
+            {
+                return Ok(Some(UNVERSIONED.to_string()));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Dry-run: get migrations required to reach the target version. Nothing is changed on
+    /// disk. `base` are unversioned directories (e.g. from the [`crate::Project`] without
+    /// version)
+    pub fn plan(&self, base: &ProjectDirs, target_version: &str) -> Result<MigrationPlan> {
+        let current_version = Self::current_version(base)?;
+
+        let steps = match &current_version {
+            Some(current) => self
+                .path(current, target_version)
+                .ok_or_else(|| Error::NoMigrationPath {
+                    from: current.clone(),
+                    to: target_version.to_string(),
+                })?
+                .into_iter()
+                .map(|(from_version, to_version)| MigrationStep {
+                    from: versioned(base, &from_version),
+                    to: versioned(base, &to_version),
+                    from_version,
+                    to_version,
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+
+        Ok(MigrationPlan {
+            current_version,
+            target_version: target_version.to_string(),
+            stamp: stamp_path(base)?,
+            steps,
+        })
+    }
+
+    /// Run migrations required to reach the target version. Stamp is updated after each
+    /// successful step, so the failed migration can be resumed. Returns the executed plan
+    pub fn migrate(&self, base: &ProjectDirs, target_version: &str) -> Result<MigrationPlan> {
+        let plan = self.plan(base, target_version)?;
+
+        for step in &plan.steps {
+            let migration = self
+                .migrations
+                .get(&step.from_version)
+                .and_then(|m| m.iter().find(|(to, _)| *to == step.to_version))
+                .map(|(_, migration)| migration)
+                .ok_or_else(|| Error::NoMigrationPath {
+                    from: step.from_version.clone(),
+                    to: step.to_version.clone(),
+                })?;
+
+            migration(step)?;
+            write_stamp(&plan.stamp, &step.to_version)?;
+        }
+
+        write_stamp(&plan.stamp, target_version)?;
+        Ok(plan)
+    }
+
+    /// Shortest chain of the registered migrations between versions
+    fn path(&self, from: &str, to: &str) -> Option<Vec<(String, String)>> {
+        let mut previous: HashMap<&str, &str> = HashMap::new();
+        let mut queue = VecDeque::from([from]);
+
+        while let Some(version) = queue.pop_front() {
+            if version == to {
+                let mut path = Vec::new();
+                let mut current = to;
+                while current != from {
+                    let prev = previous[current];
+                    path.push((prev.to_string(), current.to_string()));
+                    current = prev;
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            for (next, _) in self.migrations.get(version).into_iter().flatten() {
+                if next != from && !previous.contains_key(next.as_str()) {
+                    previous.insert(next, version);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+fn versioned(base: &ProjectDirs, version: &str) -> ProjectDirs {
+    match version {
+        UNVERSIONED => base.clone(),
+        version => base.clone().with_version(version),
+    }
+}
+
+/// Whether the name looks like [`crate::version_dir_name`], e.g. `v2`
+fn is_version_dir_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next() == Some('v') && chars.next().is_some_and(|c| c.is_ascii_digit())
+}
+
+/// Entries of the unversioned directory written before the application started to use
+/// versions. Skips the [`VERSION_STAMP`], version subdirectories (`v<digit>...`) and other
+/// project directories nested in it (e.g. `data` in the unix layout config)
+pub fn unversioned_entries(base: &ProjectDirs, dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut result = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+
+        let nested = base.0.values().any(|p| p.starts_with(&path));
+        if name != VERSION_STAMP && !is_version_dir_name(&name) && !nested {
+            result.push(path);
+        }
+    }
+    result.sort();
+    Ok(result)
+}
+
+fn stamp_path(base: &ProjectDirs) -> Result<PathBuf> {
+    base.get(&Directory::Data)
+        .map(|data| data.join(VERSION_STAMP))
+        .ok_or_else(|| Error::MissingDirectories(vec![Directory::Data]))
+}
+
+fn write_stamp(stamp: &Path, version: &str) -> Result<()> {
+    if let Some(parent) = stamp.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(stamp, version)?;
+    Ok(())
+}
+
+/// Move previous version directories to the next version. Used by
+/// [`MigrationRegistry::register_move`]. From [`UNVERSIONED`] the [`unversioned_entries`] are
+/// moved into the version subdirectory. Sources that are already gone (moved by the interrupted
+/// run) are skipped, so the step can be resumed. Returns [`Error::MigrationConflict`] (before
+/// moving anything) when both the source and its target exist
+pub fn move_dirs(step: &MigrationStep) -> Result<()> {
+    let mut moves = Vec::new();
+
+    for dir in VERSIONED_DIRECTORIES {
+        let (Some(from), Some(to)) = (step.from.get(&dir), step.to.get(&dir)) else {
+            continue;
+        };
+
+        if step.from_version == UNVERSIONED {
+            for entry in unversioned_entries(&step.from, from)? {
+                let target = to.join(entry.file_name().unwrap_or_default());
+                moves.push((entry, target));
+            }
+        } else if from.is_dir() {
+            moves.push((from.clone(), to.clone()));
+        }
+    }
+
+    if let Some((_, to)) = moves.iter().find(|(_, to)| to.exists()) {
+        return Err(Error::MigrationConflict { path: to.clone() });
+    }
+
+    for (from, to) in moves {
+        if let Some(parent) = to.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::rename(from, to)?;
+    }
+    Ok(())
+}
+
+/// State of the single [`Relocation`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum RelocationStatus {
+    /// Source exists and target is absent. Directory will be moved
+    Move,
+    /// Both source and target exist. Nothing is moved
+    Conflict,
+    /// Source is absent or it is a symlink (e.g. left by the previous relocation)
+    Missing,
+    /// Source and target are the same path
+    Unchanged,
+    /// Source or target is shared with other applications (e.g. XDG `~/.local/bin`). Nothing
+    /// is moved
+    Shared,
+}
+
+/// Move of the single directory between layouts
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct Relocation {
+    pub directory: Directory,
+    pub source: PathBuf,
+    pub target: PathBuf,
+    pub status: RelocationStatus,
+}
+
+/// Per-[`Directory`] plan of moving directories from one layout to another, e.g. from
+/// `~/.app` ([`crate::strategy::unix::Unix::unix_home`]) to XDG
+/// ([`crate::strategy::xdg::Xdg::xdg`]). Only directories present in both layouts are taken
+/// into account
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct RelocationPlan {
+    /// Relocations in order of execution. Nested sources go before their parents (e.g.
+    /// `~/.app/data` before `~/.app`)
+    pub relocations: Vec<Relocation>,
+}
+
+/// Result of the [`RelocationPlan::execute`]. Parents of the conflicting or failed directories
+/// (e.g. `~/.app` for `~/.app/state`) are reported as conflicts and left in place
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct RelocationReport {
+    /// Moved directories (new locations)
+    pub moved: ProjectDirs,
+    /// Directories with both locations present (old locations)
+    pub conflicts: ProjectDirs,
+    /// Directories that could not be moved (old locations)
+    pub failed: ProjectDirs,
+    /// Compatibility symlinks (old locations)
+    pub symlinks: ProjectDirs,
+}
+
+impl RelocationReport {
+    /// Whether there are conflicts or failures
+    pub fn has_problems(&self) -> bool {
+        !self.conflicts.0.is_empty() || !self.failed.0.is_empty()
+    }
+}
+
+/// Whether the path component is a directory named after the application: `my-app`, hidden
+/// `.my-app`, portable `my-app-data`, systemd instance `my-app@blue` or reverse-DNS
+/// `com.org.my-app`. Versions and instances are subdirectories, so they are covered by the parent
+fn project_component(component: &str, name: &str) -> bool {
+    component == name
+        || component.strip_prefix('.') == Some(name)
+        || component.strip_suffix("-data") == Some(name)
+        || component
+            .split_once('@')
+            .is_some_and(|(app, _)| app == name)
+        || component.ends_with(&format!(".{name}"))
+}
+
+/// Whether the path belongs to the project only, i.e. one of its components is named after the
+/// application (e.g. `~/.local/share/my-app` or `~/.my-app`, but not `~/.local/bin`)
+fn project_scoped(path: &Path, names: &[String]) -> bool {
+    path.components().any(|c| {
+        let component = c.as_os_str().to_string_lossy().to_lowercase();
+        names.iter().any(|name| project_component(&component, name))
+    })
+}
+
+fn relocation_status(source: &Path, target: &Path) -> RelocationStatus {
+    let source_present = std::fs::symlink_metadata(source).is_ok_and(|m| !m.is_symlink());
+
+    if source == target {
+        RelocationStatus::Unchanged
+    } else if !source_present {
+        RelocationStatus::Missing
+    } else if target.exists() {
+        RelocationStatus::Conflict
+    } else {
+        RelocationStatus::Move
+    }
+}
+
+impl RelocationPlan {
+    /// Compute plan based on the current state of the filesystem. Directories not scoped to the
+    /// project in either layout (see [`RelocationStatus::Shared`]) are skipped
+    pub fn new(project: &Project, source: &ProjectDirs, target: &ProjectDirs) -> Self {
+        let names = [
+            project.application_name_unix(),
+            project.application_name_windows(),
+        ]
+        .map(|name| name.to_lowercase());
+
+        let mut relocations: Vec<Relocation> = source
+            .0
+            .iter()
+            .filter_map(|(dir, source)| {
+                target.get(dir).map(|target| Relocation {
+                    directory: dir.clone(),
+                    source: source.clone(),
+                    status: match project_scoped(source, &names) && project_scoped(target, &names) {
+                        true => relocation_status(source, target),
+                        false => RelocationStatus::Shared,
+                    },
+                    target: target.clone(),
+                })
+            })
+            .collect();
+
+        // Deepest sources first, so parents are moved with the remaining content only.
+        // Shallowest targets first, so nested targets are not treated as conflicts
+        relocations.sort_by(|a, b| {
+            b.source
+                .components()
+                .count()
+                .cmp(&a.source.components().count())
+                .then(
+                    a.target
+                        .components()
+                        .count()
+                        .cmp(&b.target.components().count()),
+                )
+                .then(a.directory.cmp(&b.directory))
+        });
+
+        Self { relocations }
+    }
+
+    /// Relocations with both locations present
+    pub fn conflicts(&self) -> impl Iterator<Item = &Relocation> {
+        self.relocations
+            .iter()
+            .filter(|r| r.status == RelocationStatus::Conflict)
+    }
+
+    /// Move directories. Each directory is renamed atomically; between filesystems (only) it is
+    /// copied to the temporary sibling of the target first. Optionally leaves symlinks in the
+    /// old locations
+    pub fn execute(&self, symlinks: bool) -> RelocationReport {
+        let mut report = RelocationReport {
+            moved: ProjectDirs::empty(),
+            conflicts: ProjectDirs::empty(),
+            failed: ProjectDirs::empty(),
+            symlinks: ProjectDirs::empty(),
+        };
+        // Some layouts use the same path for multiple directories (e.g. config and project
+        // root)
+        let mut moved = HashMap::<PathBuf, PathBuf>::new();
+        // Parents of the conflicting or failed dirs are not moved, as they would take them along
+        let mut blocked = Vec::<PathBuf>::new();
+
+        for relocation in &self.relocations {
+            let Relocation {
+                directory,
+                source,
+                target,
+                ..
+            } = relocation;
+
+            // Skipped children do not block their parents
+            if relocation.status == RelocationStatus::Shared {
+                continue;
+            }
+
+            if moved.get(source) == Some(target) {
+                report.moved.0.insert(directory.clone(), target.clone());
+                continue;
+            }
+
+            if blocked.iter().any(|b| b.starts_with(source)) {
+                blocked.push(source.clone());
+                report.conflicts.0.insert(directory.clone(), source.clone());
+                continue;
+            }
+
+            // Re-check, as the filesystem may have changed since planning
+            match relocation_status(source, target) {
+                RelocationStatus::Move => match move_dir(source, target) {
+                    Ok(()) => {
+                        moved.insert(source.clone(), target.clone());
+                        report.moved.0.insert(directory.clone(), target.clone());
+                    }
+                    Err(_) => {
+                        blocked.push(source.clone());
+                        report.failed.0.insert(directory.clone(), source.clone());
+                    }
+                },
+                RelocationStatus::Conflict => {
+                    blocked.push(source.clone());
+                    report.conflicts.0.insert(directory.clone(), source.clone());
+                }
+                RelocationStatus::Missing
+                | RelocationStatus::Unchanged
+                | RelocationStatus::Shared => (),
+            }
+        }
+
+        if symlinks {
+            // Parents first, so nested links are created through the parent links
+            let mut links: Vec<_> = moved.iter().collect();
+            links.sort_by_key(|(source, _)| source.components().count());
+
+            for (source, target) in links {
+                if symlink_dir(target, source).is_ok() {
+                    for relocation in &self.relocations {
+                        if relocation.source == *source {
+                            report
+                                .symlinks
+                                .0
+                                .insert(relocation.directory.clone(), source.clone());
+                        }
+                    }
+                }
+            }
+        }
+
+        report
+    }
+}
+
+/// Move directory atomically. Between different filesystems copy to the temporary sibling of the target, rename it and remove the source
+fn move_dir(source: &Path, target: &Path) -> std::io::Result<()> {
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    match std::fs::rename(source, target) {
+        Ok(()) => return Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => (),
+        Err(e) => return Err(e),
+    }
+
+    let mut tmp_name = target.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(format!(".relocation-{}", std::process::id()));
+    let tmp = target.with_file_name(tmp_name);
+
+    let result = copy_dir(source, &tmp).and_then(|()| std::fs::rename(&tmp, target));
+    if result.is_err() {
+        let _ = std::fs::remove_dir_all(&tmp);
+        return result;
+    }
+
+    std::fs::remove_dir_all(source)
+}
+
+fn copy_dir(source: &Path, target: &Path) -> std::io::Result<()> {
+    std::fs::create_dir(target)?;
+    std::fs::set_permissions(target, std::fs::metadata(source)?.permissions())?;
+
+    for entry in std::fs::read_dir(source)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let target = target.join(entry.file_name());
+
+        if file_type.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else if file_type.is_symlink() {
+            symlink(&std::fs::read_link(entry.path())?, &target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn symlink(original: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(original, link)
+}
+
+#[cfg(windows)]
+fn symlink(original: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_file(original, link)
+}
+
+#[cfg(unix)]
+fn symlink_dir(original: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(original, link)
+}
+
+#[cfg(windows)]
+fn symlink_dir(original: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_dir(original, link)
+}
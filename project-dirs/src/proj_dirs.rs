@@ -1,10 +1,43 @@
-use crate::Directory;
+use crate::{CustomDirectory, Directory};
 use std::collections::HashMap;
 use std::path::PathBuf;
 
 /// List of missing directories for [`ProjectDirs`] to [`FullProjectDirs`] conversion
 pub type MissingError = Vec<Directory>;
 
+/// All directory types except [`Directory::Custom`]
+pub const BUILTIN_DIRECTORIES: [Directory; 10] = [
+    Directory::Bin,
+    Directory::Cache,
+    Directory::Config,
+    Directory::Data,
+    Directory::Include,
+    Directory::Lib,
+    Directory::Log,
+    Directory::ProjectRoot,
+    Directory::Runtime,
+    Directory::State,
+];
+
+/// Directories with the on-disk format owned by the application. They are split by the
+/// application version in [`ProjectDirs::with_version`]
+pub const VERSIONED_DIRECTORIES: [Directory; 3] =
+    [Directory::Config, Directory::Data, Directory::State];
+
+/// Directories separated between the instances of the same application (see
+/// [`ProjectDirs::with_instance`]). Others (e.g. bin, lib, include) are shared
+pub const INSTANCE_DIRECTORIES: [Directory; 4] = [
+    Directory::Data,
+    Directory::Log,
+    Directory::Runtime,
+    Directory::State,
+];
+
+/// Name of the subdirectory for the given application version, e.g. `v2`
+pub fn version_dir_name(version: &str) -> String {
+    format!("v{version}")
+}
+
 /// Project directories by directory type ([`Directory`] to [`PathBuf`] mapping)
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
@@ -15,6 +48,13 @@ impl ProjectDirs {
     pub fn get(&self, dir: &Directory) -> Option<&PathBuf> {
         self.0.get(dir)
     }
+
+    /// [`Directory::Runtime`] or the fallback directory when the strategy does not provide it
+    pub(crate) fn runtime_or(&self, fallback: Directory) -> Option<(Directory, &PathBuf)> {
+        [Directory::Runtime, fallback]
+            .into_iter()
+            .find_map(|dir| self.get(&dir).map(|path| (dir, path)))
+    }
 }
 
 /// Fully defined project directories by directory type ([`Directory`] to [`PathBuf`] mapping)
@@ -53,6 +93,42 @@ impl ProjectDirs {
     pub fn empty() -> Self {
         Self(HashMap::new())
     }
+
+    /// Append version subdirectory (see [`version_dir_name`]) to the [`VERSIONED_DIRECTORIES`],
+    /// e.g. `data/<app>` becomes `data/<app>/v2`. Not idempotent, so apply it once
+    pub fn with_version(mut self, version: &str) -> Self {
+        for dir in VERSIONED_DIRECTORIES {
+            if let Some(path) = self.0.get_mut(&dir) {
+                path.push(version_dir_name(version));
+            }
+        }
+        self
+    }
+
+    /// Append instance subdirectory to the [`INSTANCE_DIRECTORIES`], e.g. `data/<app>` becomes
+    /// `data/<app>/blue`. Not idempotent, so apply it once (e.g. through
+    /// [`crate::Project::finalize_project_dirs`] on the strategy directories)
+    pub fn with_instance(mut self, instance: &str) -> Self {
+        for dir in INSTANCE_DIRECTORIES {
+            if let Some(path) = self.0.get_mut(&dir) {
+                path.push(instance);
+            }
+        }
+        self
+    }
+
+    /// Add application-defined directories. Each one is added only if its base directory is
+    /// present. Definitions are applied in order, so custom directory may be based on the
+    /// previously defined one
+    pub fn with_custom_directories(mut self, custom_directories: &[CustomDirectory]) -> Self {
+        for custom in custom_directories {
+            if let Some(base) = self.0.get(&custom.base) {
+                let path = base.join(&custom.subpath);
+                self.0.insert(custom.directory(), path);
+            }
+        }
+        self
+    }
 }
 
 impl From<FullProjectDirs> for ProjectDirs {
@@ -0,0 +1,273 @@
+use crate::project_triplet::{
+    qualifier_cleanup, unix_name_cleanup_with_mode, windows_name_cleanup_with_mode,
+};
+use crate::{Error, NamingMode, Project, Result};
+
+/// Maximal length of the D-Bus name
+const DBUS_NAME_MAX_LENGTH: usize = 255;
+
+/// Validated project identity. Renders identifiers used by the platforms (reverse-DNS, D-Bus,
+/// Flatpak, Windows) from one place. Use [`ProjectId::builder`] to create it. Deserialization
+/// validates it the same way
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "RawProjectId"))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct ProjectId {
+    qualifier: String,
+    organization: String,
+    application: String,
+    naming_mode: NamingMode,
+}
+
+/// Deserialized [`ProjectId`] before the validation
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "schemars", schemars(rename = "ProjectId"))]
+struct RawProjectId {
+    qualifier: String,
+    organization: String,
+    application: String,
+    #[serde(default)]
+    naming_mode: NamingMode,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<RawProjectId> for ProjectId {
+    type Error = Error;
+
+    fn try_from(raw: RawProjectId) -> Result<Self> {
+        ProjectId::builder()
+            .qualifier(&raw.qualifier)
+            .organization(&raw.organization)
+            .application(&raw.application)
+            .naming_mode(raw.naming_mode)
+            .build()
+    }
+}
+
+/// Builder of the [`ProjectId`]. Each component is set by name, so the order of arguments does
+/// not matter
+#[derive(Debug, Clone, Default)]
+pub struct ProjectIdBuilder {
+    qualifier: Option<String>,
+    organization: Option<String>,
+    application: Option<String>,
+    naming_mode: NamingMode,
+}
+
+fn invalid(component: &str, value: &str, reason: &str) -> Error {
+    Error::InvalidProjectId {
+        component: component.to_string(),
+        value: value.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+/// Mode of the reverse-DNS based identifiers. They are ASCII only, so Unicode is transliterated
+fn identifier_mode(mode: NamingMode) -> NamingMode {
+    match mode {
+        NamingMode::Unicode => NamingMode::Transliterate,
+        mode => mode,
+    }
+}
+
+fn validate_name(component: &str, value: &str, mode: NamingMode) -> Result<()> {
+    if value.trim().is_empty() {
+        return Err(invalid(component, value, "is empty"));
+    }
+    if unix_name_cleanup_with_mode(value, mode).is_empty()
+        || unix_name_cleanup_with_mode(value, identifier_mode(mode)).is_empty()
+        || windows_name_cleanup_with_mode(value, mode)
+            .trim()
+            .is_empty()
+    {
+        return Err(invalid(component, value, "is empty after cleanup"));
+    }
+    Ok(())
+}
+
+fn validate_qualifier(value: &str) -> Result<()> {
+    let cleaned = qualifier_cleanup(value);
+
+    if value.trim().is_empty() {
+        return Err(invalid("qualifier", value, "is empty"));
+    }
+    if cleaned != value.to_lowercase() {
+        return Err(invalid(
+            "qualifier",
+            value,
+            "may contain only ascii letters, digits, '-' and '.'",
+        ));
+    }
+    if cleaned.chars().any(char::is_whitespace) {
+        return Err(invalid("qualifier", value, "contains whitespace"));
+    }
+    if cleaned.split('.').any(str::is_empty) {
+        return Err(invalid("qualifier", value, "contains empty label"));
+    }
+    Ok(())
+}
+
+impl ProjectIdBuilder {
+    /// Top level domain (reversed), e.g. `com` or `io.github`
+    pub fn qualifier(mut self, qualifier: &str) -> Self {
+        self.qualifier = Some(qualifier.to_string());
+        self
+    }
+
+    pub fn organization(mut self, organization: &str) -> Self {
+        self.organization = Some(organization.to_string());
+        self
+    }
+
+    pub fn application(mut self, application: &str) -> Self {
+        self.application = Some(application.to_string());
+        self
+    }
+
+    /// Handling of the non-ASCII characters. Passed to the [`ProjectId::project`]. Identifiers
+    /// (e.g. [`ProjectId::dbus_name`]) are ASCII only, so [`NamingMode::Unicode`] is
+    /// transliterated there
+    pub fn naming_mode(mut self, naming_mode: NamingMode) -> Self {
+        self.naming_mode = naming_mode;
+        self
+    }
+
+    /// Validate components. Returns [`Error::InvalidProjectId`] for missing, empty or invalid
+    /// ones (also when they are empty after the name cleanup)
+    pub fn build(self) -> Result<ProjectId> {
+        let qualifier = self.qualifier.unwrap_or_default();
+        let organization = self.organization.unwrap_or_default();
+        let application = self.application.unwrap_or_default();
+
+        validate_qualifier(&qualifier)?;
+        validate_name("organization", &organization, self.naming_mode)?;
+        validate_name("application", &application, self.naming_mode)?;
+
+        let id = ProjectId {
+            qualifier,
+            organization,
+            application,
+            naming_mode: self.naming_mode,
+        };
+
+        if id.dbus_name().len() > DBUS_NAME_MAX_LENGTH {
+            return Err(invalid(
+                "application",
+                &id.application,
+                "identifier is longer than 255 characters",
+            ));
+        }
+
+        Ok(id)
+    }
+}
+
+/// D-Bus name element cannot start with a digit
+fn escape_leading_digit(element: &str) -> String {
+    if element.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("_{element}")
+    } else {
+        element.to_string()
+    }
+}
+
+/// Make D-Bus name element. `-` is not allowed
+fn dbus_element(element: &str) -> String {
+    escape_leading_digit(&element.replace('-', "_"))
+}
+
+impl ProjectId {
+    pub fn builder() -> ProjectIdBuilder {
+        ProjectIdBuilder::default()
+    }
+
+    /// Original qualifier
+    pub fn qualifier(&self) -> &str {
+        &self.qualifier
+    }
+
+    /// Original organization name
+    pub fn organization(&self) -> &str {
+        &self.organization
+    }
+
+    /// Original application name
+    pub fn application(&self) -> &str {
+        &self.application
+    }
+
+    pub fn naming_mode(&self) -> NamingMode {
+        self.naming_mode
+    }
+
+    fn dns_elements(&self) -> Vec<String> {
+        let mode = identifier_mode(self.naming_mode);
+        qualifier_cleanup(&self.qualifier)
+            .split('.')
+            .map(str::to_string)
+            .chain([
+                unix_name_cleanup_with_mode(&self.organization, mode),
+                unix_name_cleanup_with_mode(&self.application, mode),
+            ])
+            .collect()
+    }
+
+    /// Reverse-DNS identifier, e.g. `com.example-org.magic-app-name`
+    pub fn reverse_dns(&self) -> String {
+        self.dns_elements().join(".")
+    }
+
+    /// D-Bus well-known bus name, e.g. `com.example_org.magic_app_name`
+    pub fn dbus_name(&self) -> String {
+        self.dns_elements()
+            .iter()
+            .map(|e| dbus_element(e))
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    /// Flatpak application ID, e.g. `com.example_org.magic-app-name`. Same as
+    /// [`ProjectId::dbus_name`], but the last element may contain `-`
+    pub fn flatpak_app_id(&self) -> String {
+        let elements = self.dns_elements();
+        let (last, rest) = elements
+            .split_last()
+            .expect("application is always present");
+
+        rest.iter()
+            .map(|e| dbus_element(e))
+            .chain([escape_leading_digit(last)])
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    /// Windows `Org\App` path (relative to the known folder), e.g. `Example Org\Magic App Name`
+    pub fn windows_path(&self) -> String {
+        format!(
+            "{}\\{}",
+            windows_name_cleanup_with_mode(&self.organization, self.naming_mode).trim(),
+            windows_name_cleanup_with_mode(&self.application, self.naming_mode).trim()
+        )
+    }
+
+    /// [`Project`] for this id (with the same [`NamingMode`])
+    pub fn project(&self) -> Project {
+        Project::new(&self.qualifier, &self.organization, &self.application)
+            .with_naming_mode(self.naming_mode)
+    }
+}
+
+impl From<&ProjectId> for Project {
+    fn from(value: &ProjectId) -> Self {
+        value.project()
+    }
+}
+
+impl From<ProjectId> for Project {
+    fn from(value: ProjectId) -> Self {
+        value.project()
+    }
+}
@@ -1,16 +1,59 @@
+use std::collections::BTreeMap;
+
+/// How non-ASCII characters of the project names are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "strum", derive(strum::Display, strum::EnumString))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum NamingMode {
+    /// Drop non-ASCII characters, e.g. `Żołądź project` becomes `od-project`
+    #[default]
+    Strip,
+    /// Transliterate to ASCII before the cleanup, e.g. `Żołądź project` becomes
+    /// `zoladz-project`. Characters without transliteration are dropped
+    Transliterate,
+    /// Keep Unicode letters and digits, e.g. `Żołądź project` becomes `żołądź-project`
+    Unicode,
+}
+
+/// Different names that clean up to the same directory name. See [`find_name_collisions`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct NameCollision {
+    /// Name after the cleanup
+    pub cleaned: String,
+    /// Original names (sorted)
+    pub names: Vec<String>,
+}
+
 pub fn name_cleanup(name: &str) -> String {
+    name_cleanup_with_mode(name, NamingMode::Strip)
+}
+
+/// Cleanup name for the current system using the given mode
+pub fn name_cleanup_with_mode(name: &str, mode: NamingMode) -> String {
     #[cfg(target_family = "unix")]
     {
-        unix_name_cleanup(name)
+        unix_name_cleanup_with_mode(name, mode)
     }
     #[cfg(target_family = "windows")]
     {
-        windows_name_cleanup(name)
+        windows_name_cleanup_with_mode(name, mode)
     }
 }
 
-pub fn unix_name_cleanup(name: &str) -> String {
-    name.to_ascii_lowercase()
+pub fn unix_name_cleanup_with_mode(name: &str, mode: NamingMode) -> String {
+    let keep_unicode = mode == NamingMode::Unicode;
+    let name = apply_mode(name, mode);
+    let lowercase = if keep_unicode {
+        name.to_lowercase()
+    } else {
+        name.to_ascii_lowercase()
+    };
+
+    lowercase
         .replace(" ", "-")
         .replace("_", "-")
         .replace(".", "-")
@@ -19,18 +62,21 @@ pub fn unix_name_cleanup(name: &str) -> String {
         .replace("@", "-")
         .replace("#", "-")
         .chars()
-        .filter(|c| c.is_ascii_alphanumeric() || c == &'-')
+        .filter(|c| is_name_alphanumeric(*c, keep_unicode) || c == &'-')
         .collect()
 }
 
-pub fn windows_name_cleanup(name: &str) -> String {
-    name.replace("\\", "-")
+pub fn windows_name_cleanup_with_mode(name: &str, mode: NamingMode) -> String {
+    let keep_unicode = mode == NamingMode::Unicode;
+
+    apply_mode(name, mode)
+        .replace("\\", "-")
         .replace("/", "-")
         .replace("@", "-")
         .replace("#", "-")
         .chars()
         .filter(|c| {
-            c.is_ascii_alphanumeric()
+            is_name_alphanumeric(*c, keep_unicode)
                 || c.is_whitespace()
                 || *c == '-'
                 || *c == '.'
@@ -48,3 +94,98 @@ pub fn qualifier_cleanup(qualifier: &str) -> String {
         .filter(|c| c.is_ascii_alphanumeric() || c.is_whitespace() || *c == '-' || *c == '.')
         .collect()
 }
+
+/// Find names that clean up (for the current system) to the same directory name. E.g.
+/// `Über App` and `uber-app` in the [`NamingMode::Transliterate`]
+pub fn find_name_collisions<'a>(
+    names: impl IntoIterator<Item = &'a str>,
+    mode: NamingMode,
+) -> Vec<NameCollision> {
+    let mut by_cleaned = BTreeMap::<String, Vec<String>>::new();
+
+    for name in names {
+        let cleaned_names = by_cleaned
+            .entry(name_cleanup_with_mode(name, mode))
+            .or_default();
+        if !cleaned_names.iter().any(|n| n == name) {
+            cleaned_names.push(name.to_string());
+        }
+    }
+
+    by_cleaned
+        .into_iter()
+        .filter(|(_, names)| names.len() > 1)
+        .map(|(cleaned, mut names)| {
+            names.sort();
+            NameCollision { cleaned, names }
+        })
+        .collect()
+}
+
+fn is_name_alphanumeric(c: char, keep_unicode: bool) -> bool {
+    c.is_ascii_alphanumeric() || (keep_unicode && c.is_alphanumeric())
+}
+
+fn apply_mode(name: &str, mode: NamingMode) -> String {
+    match mode {
+        NamingMode::Transliterate => transliterate(name),
+        NamingMode::Strip | NamingMode::Unicode => name.to_string(),
+    }
+}
+
+/// Transliterate Latin characters to ASCII, e.g. `Żółć` becomes `Zolc` and `ß` becomes `ss`.
+/// Other characters are left untouched
+pub fn transliterate(name: &str) -> String {
+    let mut result = String::with_capacity(name.len());
+
+    for c in name.chars() {
+        if c.is_ascii() {
+            result.push(c);
+            continue;
+        }
+
+        let lower = c.to_lowercase().next().unwrap_or(c);
+        match (transliterate_char(lower), lower == c) {
+            (Some(ascii), true) => result.push_str(ascii),
+            (Some(ascii), false) => {
+                let mut chars = ascii.chars();
+                result.extend(chars.next().map(|c| c.to_ascii_uppercase()));
+                result.extend(chars);
+            }
+            (None, _) => result.push(c),
+        }
+    }
+
+    result
+}
+
+/// ASCII replacement of the lowercase Latin letter
+fn transliterate_char(c: char) -> Option<&'static str> {
+    let ascii = match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'æ' => "ae",
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => "c",
+        'ď' | 'đ' | 'ð' => "d",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => "e",
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => "g",
+        'ĥ' | 'ħ' => "h",
+        'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => "i",
+        'ĵ' => "j",
+        'ķ' => "k",
+        'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => "l",
+        'ñ' | 'ń' | 'ņ' | 'ň' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => "o",
+        'œ' => "oe",
+        'ŕ' | 'ŗ' | 'ř' => "r",
+        'ś' | 'ŝ' | 'ş' | 'š' | 'ș' => "s",
+        'ß' => "ss",
+        'ţ' | 'ť' | 'ŧ' | 'ț' => "t",
+        'þ' => "th",
+        'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => "u",
+        'ŵ' => "w",
+        'ý' | 'ÿ' | 'ŷ' => "y",
+        'ź' | 'ż' | 'ž' => "z",
+        _ => return None,
+    };
+    Some(ascii)
+}
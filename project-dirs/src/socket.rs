@@ -0,0 +1,191 @@
+use std::path::{Path, PathBuf};
+
+use crate::dir_utils::{ProvisionPolicy, create_dir_with_mode};
+use crate::env::{EnvSource, SystemEnv};
+use crate::{Directory, Error, ProjectDirs, Result};
+
+/// Size of the `sun_path` (including the terminating nul)
+#[cfg(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "dragonfly"
+))]
+pub const SUN_PATH_MAX: usize = 104;
+
+/// Size of the `sun_path` (including the terminating nul)
+#[cfg(not(any(
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "freebsd",
+    target_os = "openbsd",
+    target_os = "netbsd",
+    target_os = "dragonfly"
+)))]
+pub const SUN_PATH_MAX: usize = 108;
+
+/// Prefix of the fallback directory, e.g. `/tmp/project-dirs-0123456789abcdef`
+pub const FALLBACK_DIR_PREFIX: &str = "project-dirs-";
+
+/// Unix socket path chosen by the [`ProjectDirs::socket_path`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct SocketPath {
+    /// Path to bind or connect to
+    pub path: PathBuf,
+    /// Path in the project directory. Same as `path`, unless it does not fit in the `sun_path`
+    pub preferred: PathBuf,
+    /// Directory type of the preferred path
+    pub directory: Directory,
+}
+
+impl SocketPath {
+    /// Whether the preferred path was too long and the hashed one is used
+    pub fn is_fallback(&self) -> bool {
+        self.path != self.preferred
+    }
+
+    /// Create the parent directory (for the server). Preferred directory is created with the
+    /// policy mode of the [`SocketPath::directory`]. Fallback directory has a predictable name in
+    /// the shared location, so it is always private (`0o700`): it is created without following
+    /// symlinks and returns [`Error::UnsafeDirectory`] unless it is a real directory owned by the
+    /// current user with exactly that mode (e.g. planted by another user in `/tmp`)
+    pub fn prepare(&self, policy: &ProvisionPolicy) -> Result<()> {
+        let Some(dir) = self.path.parent() else {
+            return Ok(());
+        };
+
+        if self.is_fallback() {
+            prepare_private_dir(dir)
+        } else {
+            if !dir.is_dir() {
+                create_dir_with_mode(dir, policy.mode(&self.directory))?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Mode of the fallback directory
+#[cfg(unix)]
+const PRIVATE_MODE: u32 = 0o700;
+
+#[cfg(unix)]
+fn prepare_private_dir(dir: &Path) -> Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+
+    // Not recursive, so an existing path (e.g. a symlink) is never followed
+    match std::fs::DirBuilder::new().mode(PRIVATE_MODE).create(dir) {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    let unsafe_dir = |reason: String| {
+        Err(Error::UnsafeDirectory {
+            path: dir.to_path_buf(),
+            reason,
+        })
+    };
+    let metadata = std::fs::symlink_metadata(dir)?;
+    let mode = metadata.permissions().mode() & 0o7777;
+
+    if !metadata.is_dir() {
+        unsafe_dir("not a directory".to_string())
+    } else if SystemEnv.uid().is_some_and(|uid| uid != metadata.uid()) {
+        unsafe_dir(format!("owned by uid {}", metadata.uid()))
+    } else if mode != PRIVATE_MODE {
+        // umask may only remove bits, e.g. from `0o700` to `0o500`
+        unsafe_dir(format!("mode is {mode:04o}, expected {PRIVATE_MODE:04o}"))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(not(unix))]
+fn prepare_private_dir(dir: &Path) -> Result<()> {
+    match std::fs::create_dir(dir) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists && dir.is_dir() => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// FNV-1a. Unlike [`std::hash::DefaultHasher`] it is stable between Rust versions, so the
+/// server and clients built with different compilers agree on the path
+fn stable_hash(path: &Path) -> u64 {
+    path.to_string_lossy()
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+/// Whether the path fits in the `sun_path` with the terminating nul
+fn fits(path: &Path) -> bool {
+    path.as_os_str().len() < SUN_PATH_MAX
+}
+
+/// Base of the fallback directory: `/run/user/<uid>` for the paths in it, `/tmp` otherwise
+fn fallback_base(preferred: &Path, env: &dyn EnvSource) -> PathBuf {
+    if let Some(uid) = env.uid() {
+        let user_runtime = PathBuf::from("/run/user").join(uid.to_string());
+        if preferred.starts_with(&user_runtime) && user_runtime.is_dir() {
+            return user_runtime;
+        }
+    }
+
+    if cfg!(unix) {
+        PathBuf::from("/tmp")
+    } else {
+        std::env::temp_dir()
+    }
+}
+
+impl ProjectDirs {
+    /// Same as [`ProjectDirs::socket_path_with_env`]. Uses uid of the current user
+    pub fn socket_path(&self, name: &str) -> Result<SocketPath> {
+        self.socket_path_with_env(name, &SystemEnv)
+    }
+
+    /// Path of the unix socket in the [`Directory::Runtime`] ([`Directory::State`] when the
+    /// runtime is not available). When it does not fit in the [`SUN_PATH_MAX`] (e.g. deep
+    /// `XDG_RUNTIME_DIR` or [`crate::dir_utils::Mounted`] paths), the socket is placed in
+    /// `/run/user/<uid>` or `/tmp`, in the [`FALLBACK_DIR_PREFIX`] directory named after the
+    /// hash of the preferred directory. Result does not depend on the filesystem state (except
+    /// `/run/user/<uid>` presence), so servers and clients agree on it. Servers should call
+    /// [`SocketPath::prepare`] before binding
+    pub fn socket_path_with_env(&self, name: &str, env: &dyn EnvSource) -> Result<SocketPath> {
+        let (directory, dir) =
+            self.runtime_or(Directory::State)
+                .ok_or(Error::MissingDirectories(vec![
+                    Directory::Runtime,
+                    Directory::State,
+                ]))?;
+        let preferred = dir.join(name);
+
+        let path = if fits(&preferred) {
+            preferred.clone()
+        } else {
+            let hash = stable_hash(dir);
+            fallback_base(&preferred, env)
+                .join(format!("{FALLBACK_DIR_PREFIX}{hash:016x}"))
+                .join(name)
+        };
+
+        if !fits(&path) {
+            return Err(Error::SocketPathTooLong {
+                path,
+                max: SUN_PATH_MAX - 1,
+            });
+        }
+        Ok(SocketPath {
+            path,
+            preferred,
+            directory,
+        })
+    }
+}
@@ -0,0 +1,45 @@
+use crate::FullProjectDirs;
+use crate::Project;
+use crate::strategy::fhs::fhs_normalize_path;
+use std::path::{Path, PathBuf};
+
+/// Default `LOCALBASE` used by the FreeBSD ports and packages
+pub const LOCALBASE: &str = "/usr/local";
+
+/// Get FreeBSD-style [`FullProjectDirs`] for software installed from ports/packages. Follows
+/// [hier(7)](https://man.freebsd.org/cgi/man.cgi?query=hier&sektion=7).
+pub trait Hier {
+    /// Get project directories for the custom `LOCALBASE` (e.g. `/opt/local`)
+    fn hier_with_localbase(&self, localbase: &Path) -> FullProjectDirs;
+
+    /// Get project directories for the default `LOCALBASE` (`/usr/local`)
+    fn hier(&self) -> FullProjectDirs {
+        self.hier_with_localbase(Path::new(LOCALBASE))
+    }
+}
+
+impl Hier for Project {
+    fn hier_with_localbase(&self, localbase: &Path) -> FullProjectDirs {
+        #[cfg(not(windows))]
+        let application_name = &self.application_name;
+
+        #[cfg(windows)]
+        let application_name = &self.application_name_unix();
+
+        FullProjectDirs {
+            cache: fhs_normalize_path(PathBuf::from("/var/cache").join(application_name)),
+            data: fhs_normalize_path(PathBuf::from("/var/db").join(application_name)),
+            log: fhs_normalize_path(PathBuf::from("/var/log").join(application_name)),
+            runtime: Some(fhs_normalize_path(
+                PathBuf::from("/var/run").join(application_name),
+            )),
+            state: fhs_normalize_path(PathBuf::from("/var/db").join(application_name)),
+            project_root: None,
+            // Values relative to the LOCALBASE
+            bin: fhs_normalize_path(localbase.join("bin")),
+            config: fhs_normalize_path(localbase.join("etc").join(application_name)),
+            include: fhs_normalize_path(localbase.join("include").join(application_name)),
+            lib: fhs_normalize_path(localbase.join("lib").join(application_name)),
+        }
+    }
+}
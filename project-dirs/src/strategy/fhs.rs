@@ -13,7 +13,7 @@ pub trait Fhs {
 }
 
 #[cfg(windows)]
-fn fhs_normalize_path(path: PathBuf) -> PathBuf {
+pub(crate) fn fhs_normalize_path(path: PathBuf) -> PathBuf {
     let components = path
         .components()
         .peekable()
@@ -24,7 +24,7 @@ fn fhs_normalize_path(path: PathBuf) -> PathBuf {
 }
 
 #[cfg(not(windows))]
-fn fhs_normalize_path(path: PathBuf) -> PathBuf {
+pub(crate) fn fhs_normalize_path(path: PathBuf) -> PathBuf {
     path
 }
 
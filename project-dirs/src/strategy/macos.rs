@@ -0,0 +1,112 @@
+use std::path::{Path, PathBuf};
+
+use crate::env::{EnvSource, SystemEnv};
+use crate::{Error, FullProjectDirs, Project, Result, project_triplet};
+
+/// Environment variables for [`Macos`] trait
+#[derive(Debug, Clone, Default)]
+pub struct MacosEnv {
+    pub home_dir: Option<PathBuf>,
+}
+
+pub const HOME: &str = "HOME";
+
+impl MacosEnv {
+    pub fn new_system() -> Self {
+        Self::from_source(&SystemEnv)
+    }
+
+    /// Read variables from the given [`EnvSource`]
+    pub fn from_source(env: &dyn EnvSource) -> Self {
+        Self {
+            home_dir: env.home_dir(),
+        }
+    }
+
+    pub fn extend_with_env(
+        &mut self,
+        other: impl Iterator<Item = (impl AsRef<str>, Option<impl AsRef<str>>)>,
+        allow_clearing: bool,
+    ) {
+        for (k, v) in other {
+            let pathbuf_new_value: Option<PathBuf> = v.and_then(|str_value| {
+                if !str_value.as_ref().is_empty() {
+                    Some(PathBuf::from(str_value.as_ref()))
+                } else {
+                    None
+                }
+            });
+            let str_key: &str = k.as_ref();
+
+            if str_key == HOME && (allow_clearing || pathbuf_new_value.is_some()) {
+                self.home_dir = pathbuf_new_value;
+            }
+        }
+    }
+}
+
+/// Get the macOS project name: `<qualifier>.<organization>.<application>`. Empty parts are
+/// skipped.
+fn macos_project_name(project: &Project) -> String {
+    [
+        project.qualifier().to_string(),
+        project_triplet::unix_name_cleanup_with_mode(
+            &project._orig_organization,
+            project.naming_mode(),
+        ),
+        project.application_name_macos(),
+    ]
+    .into_iter()
+    .filter(|part| !part.is_empty())
+    .collect::<Vec<_>>()
+    .join(".")
+}
+
+/// Build macOS directories within the given `Library` directory
+fn library_dirs(library: &Path, project_name: &str) -> FullProjectDirs {
+    let app_support = library.join("Application Support").join(project_name);
+
+    FullProjectDirs {
+        bin: app_support.join("bin"),
+        cache: library.join("Caches").join(project_name),
+        config: app_support.join("config"),
+        data: app_support.join("data"),
+        include: app_support.join("include"),
+        lib: app_support.join("lib"),
+        log: library.join("Logs").join(project_name),
+        project_root: None,
+        runtime: None,
+        state: app_support.join("state"),
+    }
+}
+
+/// Retrive [`FullProjectDirs`] for macOS using `Library` directories
+pub trait Macos {
+    /// Returns the project directories for the current user (`~/Library`) resolved from the
+    /// given env. Returns [`Error::MissingHomeDir`] if home dir cannot be resolved
+    fn macos_user_with_env(&self, env: MacosEnv) -> Result<FullProjectDirs>;
+
+    /// Returns the project directories for the whole system (`/Library`)
+    fn macos_system(&self) -> FullProjectDirs;
+
+    /// Returns the project directories for the current user (`~/Library`). Variables are
+    /// resolved from the system.
+    fn macos_user(&self) -> Result<FullProjectDirs> {
+        self.macos_user_with_env(MacosEnv::new_system())
+    }
+}
+
+impl Macos for Project {
+    fn macos_user_with_env(&self, env: MacosEnv) -> Result<FullProjectDirs> {
+        env.home_dir
+            .map(|home| library_dirs(&home.join("Library"), &macos_project_name(self)))
+            .ok_or(Error::MissingHomeDir)
+    }
+
+    fn macos_system(&self) -> FullProjectDirs {
+        let project_name = macos_project_name(self);
+        let mut dirs = library_dirs(&PathBuf::from("/Library"), &project_name);
+        dirs.runtime = Some(PathBuf::from("/var/run").join(project_name));
+        dirs
+    }
+}
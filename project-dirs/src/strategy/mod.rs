@@ -3,6 +3,12 @@
 /// Strategy for linux and similar. [FileSystem Hierarchy Standard](https://refspecs.linuxfoundation.org/FHS_3.0/fhs-3.0.pdf).
 pub mod fhs;
 
+/// Strategy for FreeBSD and similar. [hier(7)](https://man.freebsd.org/cgi/man.cgi?query=hier&sektion=7) for ports and packages.
+pub mod bsd;
+
+/// Strategy for the services started by systemd. Uses [`StateDirectory=` and similar](https://www.freedesktop.org/software/systemd/man/latest/systemd.exec.html#RuntimeDirectory=)
+pub mod systemd;
+
 /// Strategy for linux and similar for retrieving directories using
 /// [XDG Base Directories](https://specifications.freedesktop.org/basedir-spec/latest/).
 pub mod xdg;
@@ -10,7 +16,19 @@ pub mod xdg;
 /// [Unix-style project directories](https://unix.stackexchange.com/questions/21778/whats-so-special-about-directories-whose-names-begin-with-a-dot) containing everything. Mainly used for local in [`crate::Scoped`].
 pub mod unix;
 
+/// Strategy for macOS. Using [`Library` directories](https://developer.apple.com/library/archive/documentation/FileManagement/Conceptual/FileSystemProgrammingGuide/MacOSXDirectories/MacOSXDirectories.html)
+pub mod macos;
+
+/// Portable mode. Directories next to the executable, enabled by the marker file
+pub mod portable;
+
+/// Strategy for applications running in the [Flatpak](https://docs.flatpak.org/en/latest/conventions.html) or [Snap](https://snapcraft.io/docs/environment-variables) sandbox
+pub mod sandbox;
+
 /// Strategy for windows. Using [Known Folder API](https://docs.microsoft.com/en-us/windows/win32/api/shlobj_core/nf-shlobj_core-shgetknownfolderpath) and [Windows known directories](https://learn.microsoft.com/en-us/windows/win32/shell/knownfolderid#FOLDERID_Profile)
 pub mod windows;
 
-// TODO: https://man.freebsd.org/cgi/man.cgi?query=hier&apropos=0&sektion=0&manpath=FreeBSD+8.2-RELEASE&format=html
+/// Common [`Strategy`] interface and [`StrategyRegistry`] of the named strategies
+pub mod registry;
+
+pub use registry::{Strategy, StrategyRegistry};
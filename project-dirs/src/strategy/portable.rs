@@ -0,0 +1,61 @@
+use std::path::{Path, PathBuf};
+
+use crate::env::{EnvSource, SystemEnv};
+use crate::strategy::unix::unix;
+use crate::{Error, FullProjectDirs, Project, Result};
+
+/// File next to the executable that enables the portable mode
+pub const PORTABLE_MARKER: &str = "portable.txt";
+
+/// Name of the portable data directory next to the executable, e.g. `my-app-data`
+pub fn portable_dir_name(project_name: &str) -> String {
+    format!("{project_name}-data")
+}
+
+/// Directory of the executable. [`EnvSource::current_exe`] points to the binary itself
+pub(crate) fn exe_dir(env: &dyn EnvSource) -> Result<PathBuf> {
+    let exe = env.current_exe().map_err(Error::UnreadableCurrentExe)?;
+    Ok(exe.parent().map(Path::to_path_buf).unwrap_or(exe))
+}
+
+/// Portable layout rooted beside the executable. Used for USB sticks and unpacked archives
+pub trait Portable {
+    /// Directory of the executable when the portable mode is enabled, i.e. it contains
+    /// [`PORTABLE_MARKER`] or the [`portable_dir_name`] directory
+    fn portable_exe_dir_with_env(&self, env: &dyn EnvSource) -> Option<PathBuf>;
+
+    /// Directories in the [`portable_dir_name`] next to the executable (binaries are the
+    /// executable directory itself). Does not check if the portable mode is enabled
+    fn portable_with_env(&self, env: &dyn EnvSource) -> Result<FullProjectDirs>;
+
+    /// Same as [`Portable::portable_exe_dir_with_env`]. Uses the current executable
+    fn portable_exe_dir(&self) -> Option<PathBuf> {
+        self.portable_exe_dir_with_env(&SystemEnv)
+    }
+
+    /// Same as [`Portable::portable_with_env`]. Uses the current executable
+    fn portable(&self) -> Result<FullProjectDirs> {
+        self.portable_with_env(&SystemEnv)
+    }
+}
+
+impl Portable for Project {
+    fn portable_exe_dir_with_env(&self, env: &dyn EnvSource) -> Option<PathBuf> {
+        let exe_dir = exe_dir(env).ok()?;
+
+        let enabled = exe_dir.join(PORTABLE_MARKER).is_file()
+            || exe_dir
+                .join(portable_dir_name(&self.application_name))
+                .is_dir();
+        enabled.then_some(exe_dir)
+    }
+
+    fn portable_with_env(&self, env: &dyn EnvSource) -> Result<FullProjectDirs> {
+        let exe_dir = exe_dir(env)?;
+
+        Ok(FullProjectDirs {
+            bin: exe_dir.clone(),
+            ..unix(&exe_dir.join(portable_dir_name(&self.application_name)))
+        })
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+
+use crate::env::EnvSource;
+use crate::{Error, Project, ProjectDirs, Result};
+
+/// Common interface of the strategies. Allows to use them by name (see [`StrategyRegistry`])
+pub trait Strategy: Send + Sync {
+    /// Resolve project directories using the given env. Result is not finalized (see
+    /// [`Project::finalize_project_dirs`]), callers apply the instance, version etc. once
+    fn resolve(&self, project: &Project, env: &dyn EnvSource) -> Result<ProjectDirs>;
+}
+
+impl<F> Strategy for F
+where
+    F: Fn(&Project, &dyn EnvSource) -> Result<ProjectDirs> + Send + Sync,
+{
+    fn resolve(&self, project: &Project, env: &dyn EnvSource) -> Result<ProjectDirs> {
+        self(project, env)
+    }
+}
+
+/// Strategies by name. Use [`StrategyRegistry::with_defaults`] to get the built-in ones.
+#[derive(Default)]
+pub struct StrategyRegistry {
+    strategies: HashMap<String, Box<dyn Strategy>>,
+}
+
+impl StrategyRegistry {
+    /// Empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registry with the built-in strategies:
+    ///
+    /// * `current-user`, `current-system`, `current-local` - [`Project::try_project_dirs_with_env`]
+    ///   (not finalized)
+    /// * `fhs`, `fhs-local` - [`super::fhs::Fhs`]
+    /// * `bsd` - [`super::bsd::Hier`]
+    /// * `systemd` - [`super::systemd::Systemd`]
+    /// * `xdg`, `xdg-exclude-missing` - [`super::xdg::Xdg`]
+    /// * `sandbox`, `sandbox-system` - [`super::sandbox::Sandbox`]
+    /// * `portable` - [`super::portable::Portable`]
+    /// * `unix-pwd`, `unix-home`, `unix-binary` - [`super::unix::Unix`]
+    /// * `macos-user`, `macos-system` - [`super::macos::Macos`]
+    /// * `windows`, `windows-local`, `windows-shared`, `windows-system` - [`super::windows::Windows`]
+    ///   with known folders read from the env variables
+    pub fn with_defaults() -> Self {
+        use super::bsd::Hier;
+        use super::fhs::Fhs;
+        use super::macos::{Macos, MacosEnv};
+        use super::portable::Portable;
+        use super::sandbox::{Sandbox, SandboxEnv};
+        use super::systemd::{Systemd, SystemdEnv};
+        use super::unix::Unix;
+        use super::windows::{Windows, WindowsEnv};
+        use super::xdg::{Xdg, XdgEnv};
+
+        let mut registry = Self::new();
+
+        registry.register("current-user", |p: &Project, env: &dyn EnvSource| {
+            Ok(p.try_strategy_dirs_with_env(env)?.user)
+        });
+        registry.register("current-system", |p: &Project, env: &dyn EnvSource| {
+            Ok(p.try_strategy_dirs_with_env(env)?.system)
+        });
+        registry.register("current-local", |p: &Project, env: &dyn EnvSource| {
+            Ok(p.try_strategy_dirs_with_env(env)?.local)
+        });
+        registry.register("fhs", |p: &Project, _: &dyn EnvSource| Ok(p.fhs().into()));
+        registry.register("fhs-local", |p: &Project, _: &dyn EnvSource| {
+            Ok(p.fhs_local().into())
+        });
+        registry.register("bsd", |p: &Project, _: &dyn EnvSource| Ok(p.hier().into()));
+        registry.register("systemd", |p: &Project, env: &dyn EnvSource| {
+            Ok(p.systemd_with_env(SystemdEnv::from_source(env))?.into())
+        });
+        registry.register("xdg", |p: &Project, env: &dyn EnvSource| {
+            Ok(p.xdg_with_env(XdgEnv::from_source(env))?.into())
+        });
+        registry.register("xdg-exclude-missing", |p: &Project, env: &dyn EnvSource| {
+            Ok(p.xdg_with_env_exclude_missing(XdgEnv::from_source(env)))
+        });
+        registry.register("sandbox", |p: &Project, env: &dyn EnvSource| {
+            Ok(p.sandbox_user_with_env(SandboxEnv::from_source(env))?
+                .into())
+        });
+        registry.register("sandbox-system", |p: &Project, env: &dyn EnvSource| {
+            Ok(p.sandbox_system_with_env(SandboxEnv::from_source(env))?
+                .into())
+        });
+        registry.register("portable", |p: &Project, env: &dyn EnvSource| {
+            Ok(p.portable_with_env(env)?.into())
+        });
+        registry.register("unix-pwd", |p: &Project, env: &dyn EnvSource| {
+            Ok(p.unix_pwd_with_env(env)?.into())
+        });
+        registry.register("unix-home", |p: &Project, env: &dyn EnvSource| {
+            Ok(p.unix_home_with_env(env)?.into())
+        });
+        registry.register("unix-binary", |p: &Project, env: &dyn EnvSource| {
+            Ok(p.unix_binary_with_env(env)?.into())
+        });
+        registry.register("macos-user", |p: &Project, env: &dyn EnvSource| {
+            Ok(p.macos_user_with_env(MacosEnv::from_source(env))?.into())
+        });
+        registry.register("macos-system", |p: &Project, _: &dyn EnvSource| {
+            Ok(p.macos_system().into())
+        });
+        registry.register("windows", |p: &Project, env: &dyn EnvSource| {
+            Ok(p.windows_user_with_env(WindowsEnv::from_source(env)))
+        });
+        registry.register("windows-local", |p: &Project, env: &dyn EnvSource| {
+            Ok(p.windows_user_local_with_env(WindowsEnv::from_source(env)))
+        });
+        registry.register("windows-shared", |p: &Project, env: &dyn EnvSource| {
+            Ok(p.windows_user_shared_with_env(WindowsEnv::from_source(env)))
+        });
+        registry.register("windows-system", |p: &Project, env: &dyn EnvSource| {
+            Ok(p.windows_system_with_env(WindowsEnv::from_source(env)))
+        });
+
+        registry
+    }
+
+    /// Register strategy under the given name. Returns previously registered strategy (if any)
+    pub fn register(
+        &mut self,
+        name: &str,
+        strategy: impl Strategy + 'static,
+    ) -> Option<Box<dyn Strategy>> {
+        self.strategies.insert(name.to_string(), Box::new(strategy))
+    }
+
+    /// Get strategy by name
+    pub fn get(&self, name: &str) -> Option<&dyn Strategy> {
+        self.strategies.get(name).map(AsRef::as_ref)
+    }
+
+    /// Names of the registered strategies
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.strategies.keys().map(String::as_str)
+    }
+
+    /// Resolve project directories using strategy with the given name
+    pub fn resolve(
+        &self,
+        name: &str,
+        project: &Project,
+        env: &dyn EnvSource,
+    ) -> Result<ProjectDirs> {
+        self.get(name)
+            .ok_or_else(|| Error::UnknownStrategy(name.to_string()))?
+            .resolve(project, env)
+    }
+}
@@ -0,0 +1,215 @@
+use std::path::{Path, PathBuf};
+
+use crate::env::{EnvSource, SystemEnv};
+use crate::strategy::unix::unix;
+use crate::{Error, FullProjectDirs, Project, Result};
+
+pub const HOME: &str = "HOME";
+pub const FLATPAK_ID: &str = "FLATPAK_ID";
+pub const XDG_RUNTIME_DIR: &str = "XDG_RUNTIME_DIR";
+pub const SNAP_NAME: &str = "SNAP_NAME";
+pub const SNAP_USER_DATA: &str = "SNAP_USER_DATA";
+pub const SNAP_USER_COMMON: &str = "SNAP_USER_COMMON";
+pub const SNAP_DATA: &str = "SNAP_DATA";
+pub const SNAP_COMMON: &str = "SNAP_COMMON";
+
+/// Sandboxed runtime the application is running in
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum SandboxKind {
+    /// Flatpak with the given application id (`FLATPAK_ID`)
+    Flatpak(String),
+    /// Snap with the given name (`SNAP_NAME`). Name may be unknown in the custom env
+    Snap(Option<String>),
+}
+
+/// Environment variables for [`Sandbox`] trait
+#[derive(Debug, Clone, Default)]
+pub struct SandboxEnv {
+    pub home_dir: Option<PathBuf>,
+    pub flatpak_id: Option<String>,
+    pub xdg_runtime_dir: Option<PathBuf>,
+    pub snap_name: Option<String>,
+    pub snap_user_data: Option<PathBuf>,
+    pub snap_user_common: Option<PathBuf>,
+    pub snap_data: Option<PathBuf>,
+    pub snap_common: Option<PathBuf>,
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.filter(|v| !v.is_empty())
+}
+
+fn not_set(name: &str) -> Error {
+    Error::InvalidEnvValue {
+        name: name.to_string(),
+        reason: "is not set".to_string(),
+    }
+}
+
+impl SandboxEnv {
+    pub fn new_system() -> Self {
+        Self::from_source(&SystemEnv)
+    }
+
+    /// Read variables from the given [`EnvSource`]
+    pub fn from_source(env: &dyn EnvSource) -> Self {
+        let path = |name: &str| non_empty(env.var(name)).map(PathBuf::from);
+
+        Self {
+            home_dir: env.home_dir(),
+            flatpak_id: non_empty(env.var(FLATPAK_ID)),
+            xdg_runtime_dir: path(XDG_RUNTIME_DIR),
+            snap_name: non_empty(env.var(SNAP_NAME)),
+            snap_user_data: path(SNAP_USER_DATA),
+            snap_user_common: path(SNAP_USER_COMMON),
+            snap_data: path(SNAP_DATA),
+            snap_common: path(SNAP_COMMON),
+        }
+    }
+
+    pub fn extend_with_env(
+        &mut self,
+        other: impl Iterator<Item = (impl AsRef<str>, Option<impl AsRef<str>>)>,
+        allow_clearing: bool,
+    ) {
+        for (k, v) in other {
+            let new_value = non_empty(v.map(|v| v.as_ref().to_string()));
+            if !allow_clearing && new_value.is_none() {
+                continue;
+            }
+
+            match k.as_ref() {
+                HOME => self.home_dir = new_value.map(PathBuf::from),
+                FLATPAK_ID => self.flatpak_id = new_value,
+                XDG_RUNTIME_DIR => self.xdg_runtime_dir = new_value.map(PathBuf::from),
+                SNAP_NAME => self.snap_name = new_value,
+                SNAP_USER_DATA => self.snap_user_data = new_value.map(PathBuf::from),
+                SNAP_USER_COMMON => self.snap_user_common = new_value.map(PathBuf::from),
+                SNAP_DATA => self.snap_data = new_value.map(PathBuf::from),
+                SNAP_COMMON => self.snap_common = new_value.map(PathBuf::from),
+                _ => (),
+            }
+        }
+    }
+
+    /// Detect sandbox. Flatpak takes precedence over Snap
+    pub fn sandbox(&self) -> Option<SandboxKind> {
+        if let Some(id) = &self.flatpak_id {
+            Some(SandboxKind::Flatpak(id.clone()))
+        } else if self.snap_name.is_some()
+            || self.snap_user_data.is_some()
+            || self.snap_data.is_some()
+        {
+            Some(SandboxKind::Snap(self.snap_name.clone()))
+        } else {
+            None
+        }
+    }
+}
+
+/// XDG-like layout within the given directories
+fn xdg_like(
+    project_name: &str,
+    config_home: &Path,
+    data_home: &Path,
+    state_home: &Path,
+    cache_home: &Path,
+    runtime_dir: Option<&Path>,
+) -> FullProjectDirs {
+    let data = data_home.join(project_name);
+    let state = state_home.join(project_name);
+
+    FullProjectDirs {
+        bin: data.join("bin"),
+        cache: cache_home.join(project_name),
+        config: config_home.join(project_name),
+        include: data.join("include"),
+        lib: data.join("lib"),
+        log: state.join("log"),
+        project_root: None,
+        runtime: runtime_dir.map(|p| p.join(project_name)),
+        data,
+        state,
+    }
+}
+
+/// Retrieve [`FullProjectDirs`] inside Flatpak or Snap sandbox
+pub trait Sandbox {
+    /// Directories of the current user in the detected sandbox. Returns
+    /// [`Error::SandboxNotDetected`] outside of the sandbox
+    fn sandbox_user_with_env(&self, env: SandboxEnv) -> Result<FullProjectDirs>;
+
+    /// System-wide directories in the detected sandbox (Snap only). Returns
+    /// [`Error::SandboxNotDetected`] outside of the sandbox and [`Error::UnsupportedPlatform`]
+    /// in Flatpak
+    fn sandbox_system_with_env(&self, env: SandboxEnv) -> Result<FullProjectDirs>;
+
+    /// Same as [`Sandbox::sandbox_user_with_env`]. Variables are resolved from the system
+    fn sandbox_user(&self) -> Result<FullProjectDirs> {
+        self.sandbox_user_with_env(SandboxEnv::new_system())
+    }
+
+    /// Same as [`Sandbox::sandbox_system_with_env`]. Variables are resolved from the system
+    fn sandbox_system(&self) -> Result<FullProjectDirs> {
+        self.sandbox_system_with_env(SandboxEnv::new_system())
+    }
+}
+
+impl Sandbox for Project {
+    fn sandbox_user_with_env(&self, env: SandboxEnv) -> Result<FullProjectDirs> {
+        match env.sandbox() {
+            Some(SandboxKind::Flatpak(id)) => {
+                let base = env
+                    .home_dir
+                    .ok_or(Error::MissingHomeDir)?
+                    .join(".var")
+                    .join("app")
+                    .join(&id);
+                let runtime_dir = env.xdg_runtime_dir.map(|p| p.join("app").join(&id));
+
+                Ok(xdg_like(
+                    &self.application_name,
+                    &base.join("config"),
+                    &base.join("data"),
+                    &base.join(".local").join("state"),
+                    &base.join("cache"),
+                    runtime_dir.as_deref(),
+                ))
+            }
+            Some(SandboxKind::Snap(_)) => {
+                let user_data = env.snap_user_data.ok_or_else(|| not_set(SNAP_USER_DATA))?;
+                // Cache should not be copied between revisions
+                let user_common = env.snap_user_common.unwrap_or(user_data.clone());
+
+                Ok(xdg_like(
+                    &self.application_name,
+                    &user_data.join(".config"),
+                    &user_data.join(".local").join("share"),
+                    &user_data.join(".local").join("state"),
+                    &user_common.join(".cache"),
+                    env.xdg_runtime_dir.as_deref(),
+                ))
+            }
+            None => Err(Error::SandboxNotDetected),
+        }
+    }
+
+    fn sandbox_system_with_env(&self, env: SandboxEnv) -> Result<FullProjectDirs> {
+        match env.sandbox() {
+            Some(SandboxKind::Flatpak(_)) => Err(Error::UnsupportedPlatform("flatpak".to_string())),
+            Some(SandboxKind::Snap(_)) => {
+                let data = env.snap_data.ok_or_else(|| not_set(SNAP_DATA))?;
+                let common = env.snap_common.unwrap_or(data.clone());
+
+                let mut dirs = unix(&data.join(&self.application_name));
+                dirs.cache = common.join(&self.application_name).join("cache");
+                dirs.runtime = None;
+                Ok(dirs)
+            }
+            None => Err(Error::SandboxNotDetected),
+        }
+    }
+}
@@ -0,0 +1,509 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::dir_utils::ProvisionPolicy;
+use crate::env::{EnvSource, MemoryEnv, SystemEnv};
+use crate::strategy::fhs::Fhs;
+use crate::{Directory, Error, FullProjectDirs, Project, ProjectDirs, Result};
+
+pub const STATE_DIRECTORY: &str = "STATE_DIRECTORY";
+pub const CACHE_DIRECTORY: &str = "CACHE_DIRECTORY";
+pub const LOGS_DIRECTORY: &str = "LOGS_DIRECTORY";
+pub const CONFIGURATION_DIRECTORY: &str = "CONFIGURATION_DIRECTORY";
+pub const RUNTIME_DIRECTORY: &str = "RUNTIME_DIRECTORY";
+
+/// Environment variables for [`Systemd`] trait. Each variable is a colon-separated list of the
+/// paths (one per directory listed in the unit)
+#[derive(Debug, Clone, Default)]
+pub struct SystemdEnv {
+    /// `StateDirectory=`
+    pub state_directory: Vec<PathBuf>,
+    /// `CacheDirectory=`
+    pub cache_directory: Vec<PathBuf>,
+    /// `LogsDirectory=`
+    pub logs_directory: Vec<PathBuf>,
+    /// `ConfigurationDirectory=`
+    pub configuration_directory: Vec<PathBuf>,
+    /// `RuntimeDirectory=`
+    pub runtime_directory: Vec<PathBuf>,
+}
+
+/// Split colon separated list of paths. Empty elements are skipped
+fn systemd_paths_list(str_val: &str) -> Vec<PathBuf> {
+    str_val
+        .split(':')
+        .filter(|p| !p.is_empty())
+        .map(PathBuf::from)
+        .collect()
+}
+
+impl SystemdEnv {
+    pub fn new_system() -> Self {
+        Self::from_source(&SystemEnv)
+    }
+
+    /// Read variables from the given [`EnvSource`]
+    pub fn from_source(env: &dyn EnvSource) -> Self {
+        let list = |name: &str| {
+            env.var(name)
+                .map(|v| systemd_paths_list(&v))
+                .unwrap_or_default()
+        };
+
+        Self {
+            state_directory: list(STATE_DIRECTORY),
+            cache_directory: list(CACHE_DIRECTORY),
+            logs_directory: list(LOGS_DIRECTORY),
+            configuration_directory: list(CONFIGURATION_DIRECTORY),
+            runtime_directory: list(RUNTIME_DIRECTORY),
+        }
+    }
+
+    pub fn extend_with_env(
+        &mut self,
+        other: impl Iterator<Item = (impl AsRef<str>, Option<impl AsRef<str>>)>,
+        allow_clearing: bool,
+    ) {
+        for (k, v) in other {
+            let new_value = v
+                .map(|v| systemd_paths_list(v.as_ref()))
+                .unwrap_or_default();
+            if !allow_clearing && new_value.is_empty() {
+                continue;
+            }
+
+            match k.as_ref() {
+                STATE_DIRECTORY => self.state_directory = new_value,
+                CACHE_DIRECTORY => self.cache_directory = new_value,
+                LOGS_DIRECTORY => self.logs_directory = new_value,
+                CONFIGURATION_DIRECTORY => self.configuration_directory = new_value,
+                RUNTIME_DIRECTORY => self.runtime_directory = new_value,
+                _ => (),
+            }
+        }
+    }
+
+    /// Check if none of the directories is passed by systemd
+    pub fn is_empty(&self) -> bool {
+        self.state_directory.is_empty()
+            && self.cache_directory.is_empty()
+            && self.logs_directory.is_empty()
+            && self.configuration_directory.is_empty()
+            && self.runtime_directory.is_empty()
+    }
+}
+
+/// Choose the directory of the project. When unit lists several directories, the one named after
+/// the project is preferred, then the first one. Systemd always passes absolute paths.
+///
+/// Instance segment (`app/%i` in the unit) is removed, as it is added to all of the
+/// [`crate::INSTANCE_DIRECTORIES`] later (see [`Project::finalize_project_dirs`])
+fn systemd_directory(
+    name: &str,
+    paths: &[PathBuf],
+    project_name: &str,
+    instance: Option<&str>,
+) -> Result<Option<PathBuf>> {
+    if let Some(relative) = paths.iter().find(|p| !p.is_absolute()) {
+        return Err(Error::InvalidEnvValue {
+            name: name.to_string(),
+            reason: format!("{} is not an absolute path", relative.display()),
+        });
+    }
+
+    let paths: Vec<&Path> = paths
+        .iter()
+        .map(|p| match (instance, p.parent()) {
+            (Some(instance), Some(parent)) if p.file_name().is_some_and(|f| f == instance) => {
+                parent
+            }
+            _ => p.as_path(),
+        })
+        .collect();
+
+    Ok(paths
+        .iter()
+        .find(|p| p.file_name().is_some_and(|f| f == project_name))
+        .or(paths.first())
+        .map(|p| p.to_path_buf()))
+}
+
+/// Get [`FullProjectDirs`] of the service using directories passed by systemd (e.g.
+/// `StateDirectory=` as `$STATE_DIRECTORY`). Works with `DynamicUser=`
+pub trait Systemd {
+    /// Directories missing in the env fall back to [`Fhs::fhs`]. Returns
+    /// [`Error::InvalidEnvValue`] for the relative paths. Instance segment (e.g.
+    /// `StateDirectory=app/%i`) is removed, see [`Project::finalize_project_dirs`]
+    fn systemd_with_env(&self, env: SystemdEnv) -> Result<FullProjectDirs>;
+
+    /// Same as [`Systemd::systemd_with_env`]. Variables are resolved from the system
+    fn systemd(&self) -> Result<FullProjectDirs> {
+        self.systemd_with_env(SystemdEnv::new_system())
+    }
+}
+
+impl Systemd for Project {
+    fn systemd_with_env(&self, env: SystemdEnv) -> Result<FullProjectDirs> {
+        let fhs = self.fhs();
+        let name = &self.application_name;
+        let or_fhs = |found: Option<PathBuf>, fallback: &Path| {
+            found.unwrap_or_else(|| fallback.to_path_buf())
+        };
+
+        // Cache and config are shared between the instances
+        let instance = self.instance();
+
+        let state = systemd_directory(STATE_DIRECTORY, &env.state_directory, name, instance)?;
+        let cache = systemd_directory(CACHE_DIRECTORY, &env.cache_directory, name, None)?;
+        let log = systemd_directory(LOGS_DIRECTORY, &env.logs_directory, name, instance)?;
+        let config = systemd_directory(
+            CONFIGURATION_DIRECTORY,
+            &env.configuration_directory,
+            name,
+            None,
+        )?;
+        let runtime = systemd_directory(RUNTIME_DIRECTORY, &env.runtime_directory, name, instance)?;
+
+        Ok(FullProjectDirs {
+            // FHS keeps data and state together in `/var/lib`
+            data: or_fhs(state.clone(), &fhs.data),
+            state: or_fhs(state, &fhs.state),
+            cache: or_fhs(cache, &fhs.cache),
+            log: or_fhs(log, &fhs.log),
+            config: or_fhs(config, &fhs.config),
+            runtime: runtime.or(fhs.runtime),
+            ..fhs
+        })
+    }
+}
+
+/// Directory managed by systemd with the `[Service]` directive (e.g. `StateDirectory=`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub enum UnitDirectory {
+    /// `RuntimeDirectory=` in `/run`
+    Runtime,
+    /// `StateDirectory=` in `/var/lib`
+    State,
+    /// `CacheDirectory=` in `/var/cache`
+    Cache,
+    /// `LogsDirectory=` in `/var/log`
+    Logs,
+    /// `ConfigurationDirectory=` in `/etc`
+    Configuration,
+}
+
+impl UnitDirectory {
+    /// Name of the directive, e.g. `StateDirectory`
+    pub fn directive(&self) -> &'static str {
+        match self {
+            Self::Runtime => "RuntimeDirectory",
+            Self::State => "StateDirectory",
+            Self::Cache => "CacheDirectory",
+            Self::Logs => "LogsDirectory",
+            Self::Configuration => "ConfigurationDirectory",
+        }
+    }
+
+    /// Env variable passed to the service, e.g. [`STATE_DIRECTORY`]
+    pub fn env_variable(&self) -> &'static str {
+        match self {
+            Self::Runtime => RUNTIME_DIRECTORY,
+            Self::State => STATE_DIRECTORY,
+            Self::Cache => CACHE_DIRECTORY,
+            Self::Logs => LOGS_DIRECTORY,
+            Self::Configuration => CONFIGURATION_DIRECTORY,
+        }
+    }
+
+    /// Base path of the system service directories
+    pub fn base(&self) -> &'static Path {
+        Path::new(match self {
+            Self::Runtime => "/run",
+            Self::State => "/var/lib",
+            Self::Cache => "/var/cache",
+            Self::Logs => "/var/log",
+            Self::Configuration => "/etc",
+        })
+    }
+
+    /// Directive for the given directory type. [`Directory::Data`] shares `StateDirectory=` with
+    /// [`Directory::State`]
+    pub fn for_directory(dir: &Directory) -> Option<Self> {
+        match dir {
+            Directory::Runtime => Some(Self::Runtime),
+            Directory::Data | Directory::State => Some(Self::State),
+            Directory::Cache => Some(Self::Cache),
+            Directory::Log => Some(Self::Logs),
+            Directory::Config => Some(Self::Configuration),
+            _ => None,
+        }
+    }
+
+    /// Path relative to the [`UnitDirectory::base`] (as used by the directive). `private` subdir
+    /// of the `DynamicUser=` is skipped
+    pub fn relative_path(&self, path: &Path) -> Option<PathBuf> {
+        let relative = path.strip_prefix(self.base()).ok()?;
+        let relative = relative.strip_prefix("private").unwrap_or(relative);
+
+        if relative.as_os_str().is_empty() {
+            None
+        } else {
+            Some(relative.to_path_buf())
+        }
+    }
+
+    /// Directory type used for the mode of the directive
+    pub fn directory(&self) -> Directory {
+        match self {
+            Self::Runtime => Directory::Runtime,
+            Self::State => Directory::State,
+            Self::Cache => Directory::Cache,
+            Self::Logs => Directory::Log,
+            Self::Configuration => Directory::Config,
+        }
+    }
+}
+
+/// Instance name in the template unit
+pub const INSTANCE_SPECIFIER: &str = "%i";
+
+/// Directories the service writes to. State goes before data, as its mode is used when both
+/// share the directory (see [`UnitDirectory::directory`])
+const WRITABLE_DIRECTORIES: [Directory; 5] = [
+    Directory::State,
+    Directory::Data,
+    Directory::Cache,
+    Directory::Log,
+    Directory::Runtime,
+];
+
+/// `[Service]` directives creating the project directories, e.g. `StateDirectory=app` with
+/// `StateDirectoryMode=0755`. Directories outside of the systemd bases (see
+/// [`UnitDirectory::base`]) are only listed in `ReadWritePaths=`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct ServiceDirectives {
+    /// Paths relative to the [`UnitDirectory::base`]
+    pub directories: BTreeMap<UnitDirectory, Vec<PathBuf>>,
+    /// Mode of each directive
+    pub modes: BTreeMap<UnitDirectory, u32>,
+    /// Writable directories (absolute)
+    pub read_write_paths: Vec<PathBuf>,
+}
+
+impl ServiceDirectives {
+    /// Directives for the given directories. Modes are taken from the policy (see
+    /// [`UnitDirectory::directory`])
+    pub fn new(dirs: &ProjectDirs, policy: &ProvisionPolicy) -> Self {
+        let mut result = Self::default();
+
+        for (dir, path) in &dirs.0 {
+            if let Some(unit) = UnitDirectory::for_directory(dir)
+                && let Some(relative) = unit.relative_path(path)
+            {
+                result.directories.entry(unit).or_default().push(relative);
+                result.modes.insert(unit, policy.mode(&unit.directory()));
+            }
+
+            if WRITABLE_DIRECTORIES.contains(dir) {
+                result.read_write_paths.push(path.clone());
+            }
+        }
+
+        for paths in result.directories.values_mut() {
+            paths.sort();
+            paths.dedup();
+        }
+        result.read_write_paths.sort();
+        result.read_write_paths.dedup();
+        result
+    }
+
+    /// Directives for the FHS system scope of the project (see [`Fhs::fhs`]). Instance of the
+    /// project is replaced with the `%i` specifier of the template unit (`app@.service`). Env
+    /// overrides of the project are ignored, as the env of the generating process is not the
+    /// env of the service
+    pub fn for_project(project: &Project, policy: &ProvisionPolicy) -> Self {
+        let instance = project.instance().map(|_| INSTANCE_SPECIFIER);
+        Self::new(
+            &project.finalize_with_instance(project.fhs().into(), instance, &MemoryEnv::new()),
+            policy,
+        )
+    }
+}
+
+/// Quote value containing whitespace
+fn unit_value(path: &Path) -> String {
+    let value = path.to_string_lossy();
+    if value.contains(char::is_whitespace) {
+        format!("\"{value}\"")
+    } else {
+        value.into_owned()
+    }
+}
+
+fn unit_values(paths: &[PathBuf]) -> String {
+    paths
+        .iter()
+        .map(|p| unit_value(p))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+impl std::fmt::Display for ServiceDirectives {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "[Service]")?;
+        for (unit, paths) in &self.directories {
+            writeln!(f, "{}={}", unit.directive(), unit_values(paths))?;
+            if let Some(mode) = self.modes.get(unit) {
+                writeln!(f, "{}Mode={:04o}", unit.directive(), mode)?;
+            }
+        }
+        if !self.read_write_paths.is_empty() {
+            writeln!(f, "ReadWritePaths={}", unit_values(&self.read_write_paths))?;
+        }
+        Ok(())
+    }
+}
+
+/// Owner and modes of the system directories. Used for `tmpfiles.d` (see [`tmpfiles_entries`])
+/// and `sysusers.d` (see [`sysusers_entries`])
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct OwnershipPolicy {
+    /// Service user
+    pub user: String,
+    /// Group of the directories. Defaults to the user
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub group: Option<String>,
+    /// Modes of the directories
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub modes: ProvisionPolicy,
+    /// Age of the [`Directory::Cache`] files to clean up, e.g. `30d`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub cache_age: Option<String>,
+    /// Age of the [`Directory::Runtime`] files to clean up, e.g. `10d`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub runtime_age: Option<String>,
+}
+
+impl OwnershipPolicy {
+    /// Directories owned by the user (and group of the same name) with the default modes
+    pub fn new(user: &str) -> Self {
+        Self {
+            user: user.to_string(),
+            group: None,
+            modes: ProvisionPolicy::default(),
+            cache_age: None,
+            runtime_age: None,
+        }
+    }
+
+    pub fn with_group(mut self, group: &str) -> Self {
+        self.group = Some(group.to_string());
+        self
+    }
+
+    pub fn with_modes(mut self, modes: ProvisionPolicy) -> Self {
+        self.modes = modes;
+        self
+    }
+
+    pub fn with_cache_age(mut self, age: &str) -> Self {
+        self.cache_age = Some(age.to_string());
+        self
+    }
+
+    pub fn with_runtime_age(mut self, age: &str) -> Self {
+        self.runtime_age = Some(age.to_string());
+        self
+    }
+
+    /// Group of the directories
+    pub fn group(&self) -> &str {
+        self.group.as_deref().unwrap_or(&self.user)
+    }
+
+    /// Cleanup age of the given directory type. Only cache and runtime are cleaned up
+    pub fn age(&self, dir: &Directory) -> Option<&str> {
+        match dir {
+            Directory::Cache => self.cache_age.as_deref(),
+            Directory::Runtime => self.runtime_age.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+/// `d` line of the [tmpfiles.d(5)](https://www.freedesktop.org/software/systemd/man/latest/tmpfiles.d.html)
+/// creating the directory, e.g. `d /var/cache/app 0755 app app 30d`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+pub struct TmpfilesEntry {
+    pub path: PathBuf,
+    pub mode: u32,
+    pub user: String,
+    pub group: String,
+    /// Files older than this are removed. `None` disables cleanup
+    pub age: Option<String>,
+}
+
+impl std::fmt::Display for TmpfilesEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "d {} {:04o} {} {} {}",
+            unit_value(&self.path),
+            self.mode,
+            self.user,
+            self.group,
+            self.age.as_deref().unwrap_or("-")
+        )
+    }
+}
+
+/// `tmpfiles.d` entries creating the writable directories (cache, data, log, runtime and state)
+/// of the system scope. Sorted by path, so parents are created first
+pub fn tmpfiles_entries(dirs: &ProjectDirs, policy: &OwnershipPolicy) -> Vec<TmpfilesEntry> {
+    let mut entries: Vec<TmpfilesEntry> = WRITABLE_DIRECTORIES
+        .iter()
+        .filter_map(|dir| {
+            dirs.get(dir).map(|path| TmpfilesEntry {
+                path: path.clone(),
+                mode: policy.modes.mode(dir),
+                user: policy.user.clone(),
+                group: policy.group().to_string(),
+                age: policy.age(dir).map(str::to_string),
+            })
+        })
+        .collect();
+
+    // Stable sort keeps state before data. They share the same directory in FHS
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    entries.dedup_by(|a, b| a.path == b.path);
+    entries
+}
+
+/// [sysusers.d(5)](https://www.freedesktop.org/software/systemd/man/latest/sysusers.d.html)
+/// lines creating the service user (and the group, if it differs from the user)
+pub fn sysusers_entries(policy: &OwnershipPolicy, description: &str) -> Vec<String> {
+    let user = format!(
+        "u {} - \"{}\" - -",
+        policy.user,
+        description.replace('"', "'")
+    );
+
+    match &policy.group {
+        Some(group) if group != &policy.user => vec![
+            format!("g {group} -"),
+            user,
+            format!("m {} {group}", policy.user),
+        ],
+        _ => vec![user],
+    }
+}
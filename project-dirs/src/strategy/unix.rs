@@ -1,6 +1,8 @@
 use std::path::Path;
 
-use crate::{FullProjectDirs, Project};
+use crate::env::{EnvSource, SystemEnv};
+use crate::strategy::portable::exe_dir;
+use crate::{Error, FullProjectDirs, Project, Result};
 
 /// Get a unix dir for a given path. Independent from project
 pub fn unix(full_project_path: &Path) -> FullProjectDirs {
@@ -30,13 +32,29 @@ pub trait Unix {
 
     /// Get path to the unix-style directories for the current working directory (PWD). Assumes
     /// prefix is ".".
-    fn unix_pwd(&self) -> Result<FullProjectDirs, std::io::Error>;
+    fn unix_pwd(&self) -> Result<FullProjectDirs> {
+        self.unix_pwd_with_env(&SystemEnv)
+    }
 
     /// Get path to the unix-style directories for the current user. Assumes prefix is ".".
-    fn unix_home(&self) -> Option<FullProjectDirs>;
+    fn unix_home(&self) -> Result<FullProjectDirs> {
+        self.unix_home_with_env(&SystemEnv)
+    }
+
+    /// Get path to the unix-style directories in the directory of the current binary. Assumes
+    /// prefix is ".". See [`super::portable::Portable`] for the portable mode
+    fn unix_binary(&self) -> Result<FullProjectDirs> {
+        self.unix_binary_with_env(&SystemEnv)
+    }
+
+    /// Same as [`Unix::unix_pwd`], but uses the given [`EnvSource`]
+    fn unix_pwd_with_env(&self, env: &dyn EnvSource) -> Result<FullProjectDirs>;
+
+    /// Same as [`Unix::unix_home`], but uses the given [`EnvSource`]
+    fn unix_home_with_env(&self, env: &dyn EnvSource) -> Result<FullProjectDirs>;
 
-    /// Get path to the unix-style directories for the current binary. Assumes prefix is ".".
-    fn unix_binary(&self) -> Result<FullProjectDirs, std::io::Error>;
+    /// Same as [`Unix::unix_binary`], but uses the given [`EnvSource`]
+    fn unix_binary_with_env(&self, env: &dyn EnvSource) -> Result<FullProjectDirs>;
 }
 
 impl Unix for Project {
@@ -47,15 +65,19 @@ impl Unix for Project {
         unix(&full_project_path)
     }
 
-    fn unix_pwd(&self) -> Result<FullProjectDirs, std::io::Error> {
-        std::env::current_dir().map(|path| self.unix_prefixed(&path, "."))
+    fn unix_pwd_with_env(&self, env: &dyn EnvSource) -> Result<FullProjectDirs> {
+        env.current_dir()
+            .map(|path| self.unix_prefixed(&path, "."))
+            .map_err(Error::UnreadableCurrentDir)
     }
 
-    fn unix_home(&self) -> Option<FullProjectDirs> {
-        crate::dir_utils::home_dir().map(|path| self.unix_prefixed(&path, "."))
+    fn unix_home_with_env(&self, env: &dyn EnvSource) -> Result<FullProjectDirs> {
+        env.home_dir()
+            .map(|path| self.unix_prefixed(&path, "."))
+            .ok_or(Error::MissingHomeDir)
     }
 
-    fn unix_binary(&self) -> Result<FullProjectDirs, std::io::Error> {
-        std::env::current_exe().map(|path| self.unix_prefixed(&path, "."))
+    fn unix_binary_with_env(&self, env: &dyn EnvSource) -> Result<FullProjectDirs> {
+        exe_dir(env).map(|path| self.unix_prefixed(&path, "."))
     }
 }
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
+use crate::env::EnvSource;
 use crate::{Directory, Project, ProjectDirs};
 
 #[cfg(target_os = "windows")]
@@ -89,6 +90,23 @@ pub const ROAMING_APP_DATA: &str = "%RoamingAppData%";
 pub const LOCAL_APP_DATA: &str = "%LocalAppData%";
 
 impl WindowsEnv {
+    /// Read known folders from the env variables (`ProgramFiles`, `ProgramData`, `APPDATA` and
+    /// `LOCALAPPDATA`) of the given [`EnvSource`]
+    pub fn from_source(env: &dyn EnvSource) -> Self {
+        let variable = |name: &str| {
+            env.var(name)
+                .filter(|value| !value.is_empty())
+                .map(PathBuf::from)
+        };
+
+        Self {
+            program_files: variable("ProgramFiles"),
+            program_data: variable("ProgramData"),
+            roaming_app_data: variable("APPDATA"),
+            local_app_data: variable("LOCALAPPDATA"),
+        }
+    }
+
     pub fn extend_with_env(
         &mut self,
         other: impl Iterator<Item = (impl AsRef<str>, Option<impl AsRef<str>>)>,
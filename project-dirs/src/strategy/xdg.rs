@@ -1,6 +1,10 @@
-use std::{collections::HashMap, path::PathBuf};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
 
-use crate::{Directory, FullProjectDirs, Project, ProjectDirs};
+use crate::env::{EnvSource, SystemEnv};
+use crate::{Directory, Error, FullProjectDirs, Project, ProjectDirs};
 
 /// Environment variables for [`Xdg`] trait
 #[derive(Debug, Clone, Default)]
@@ -11,10 +15,14 @@ pub struct XdgEnv {
     pub xdg_state_home: Option<PathBuf>,
     pub xdg_cache_home: Option<PathBuf>,
     pub xdg_runtime_dir: Option<PathBuf>,
+    /// Preference-ordered `XDG_DATA_DIRS`. `None` means the default (`/usr/local/share:/usr/share`)
+    pub xdg_data_dirs: Option<Vec<PathBuf>>,
+    /// Preference-ordered `XDG_CONFIG_DIRS`. `None` means the default (`/etc/xdg`)
+    pub xdg_config_dirs: Option<Vec<PathBuf>>,
 }
 
-fn xdg_variable(varname: &str) -> Option<PathBuf> {
-    let maybe_str_val = std::env::var(varname).ok();
+fn xdg_variable(env: &dyn EnvSource, varname: &str) -> Option<PathBuf> {
+    let maybe_str_val = env.var(varname);
 
     maybe_str_val.and_then(|str_val| {
         if str_val.is_empty() {
@@ -25,6 +33,33 @@ fn xdg_variable(varname: &str) -> Option<PathBuf> {
     })
 }
 
+/// Split colon separated list of paths. Relative paths are ignored (as required by the spec).
+fn xdg_paths_list(str_val: &str) -> Option<Vec<PathBuf>> {
+    let paths: Vec<PathBuf> = str_val
+        .split(':')
+        .map(PathBuf::from)
+        .filter(|p| p.is_absolute())
+        .collect();
+
+    if paths.is_empty() { None } else { Some(paths) }
+}
+
+fn xdg_list_variable(env: &dyn EnvSource, varname: &str) -> Option<Vec<PathBuf>> {
+    env.var(varname)
+        .and_then(|str_val| xdg_paths_list(&str_val))
+}
+
+fn default_xdg_data_dirs() -> Vec<PathBuf> {
+    vec![
+        PathBuf::from("/usr/local/share"),
+        PathBuf::from("/usr/share"),
+    ]
+}
+
+fn default_xdg_config_dirs() -> Vec<PathBuf> {
+    vec![PathBuf::from("/etc/xdg")]
+}
+
 pub const HOME: &str = "HOME";
 pub const XDG_DATA_HOME: &str = "XDG_DATA_HOME";
 pub const XDG_CONFIG_HOME: &str = "XDG_CONFIG_HOME";
@@ -36,13 +71,20 @@ pub const XDG_CONFIG_DIRS: &str = "XDG_CONFIG_DIRS";
 
 impl XdgEnv {
     pub fn new_system() -> Self {
+        Self::from_source(&SystemEnv)
+    }
+
+    /// Read variables from the given [`EnvSource`]
+    pub fn from_source(env: &dyn EnvSource) -> Self {
         Self {
-            home_dir: crate::dir_utils::home_dir(),
-            xdg_data_home: xdg_variable(XDG_DATA_HOME),
-            xdg_config_home: xdg_variable(XDG_CONFIG_HOME),
-            xdg_state_home: xdg_variable(XDG_STATE_HOME),
-            xdg_cache_home: xdg_variable(XDG_CACHE_HOME),
-            xdg_runtime_dir: xdg_variable(XDG_RUNTIME_DIR),
+            home_dir: env.home_dir(),
+            xdg_data_home: xdg_variable(env, XDG_DATA_HOME),
+            xdg_config_home: xdg_variable(env, XDG_CONFIG_HOME),
+            xdg_state_home: xdg_variable(env, XDG_STATE_HOME),
+            xdg_cache_home: xdg_variable(env, XDG_CACHE_HOME),
+            xdg_runtime_dir: xdg_variable(env, XDG_RUNTIME_DIR),
+            xdg_data_dirs: xdg_list_variable(env, XDG_DATA_DIRS),
+            xdg_config_dirs: xdg_list_variable(env, XDG_CONFIG_DIRS),
         }
     }
 
@@ -52,6 +94,20 @@ impl XdgEnv {
         allow_clearing: bool,
     ) {
         for (k, v) in other {
+            let str_key: &str = k.as_ref();
+
+            if matches!(str_key, XDG_DATA_DIRS | XDG_CONFIG_DIRS) {
+                let list_value = v.and_then(|str_value| xdg_paths_list(str_value.as_ref()));
+
+                if allow_clearing || list_value.is_some() {
+                    match str_key {
+                        XDG_DATA_DIRS => self.xdg_data_dirs = list_value,
+                        _ => self.xdg_config_dirs = list_value,
+                    }
+                }
+                continue;
+            }
+
             let pathbuf_vaule: Option<PathBuf> = v.and_then(|str_value| {
                 if !str_value.as_ref().is_empty() {
                     Some(PathBuf::from(str_value.as_ref()))
@@ -59,7 +115,6 @@ impl XdgEnv {
                     None
                 }
             });
-            let str_key: &str = k.as_ref();
 
             if allow_clearing || pathbuf_vaule.is_some() {
                 match str_key {
@@ -86,26 +141,48 @@ impl XdgEnv {
 
 /// Read XDG_DATA_DIRS to the vector
 pub fn xdg_data_dirs() -> Vec<PathBuf> {
-    std::env::var(XDG_DATA_DIRS)
-        .map(|string| string.split(':').map(PathBuf::from).collect())
-        .unwrap_or(vec![
-            PathBuf::from("/usr/local/share"),
-            PathBuf::from("/usr/share"),
-        ])
+    xdg_data_dirs_with_env(&SystemEnv)
+}
+
+/// Read XDG_DATA_DIRS from the given [`EnvSource`] to the vector
+pub fn xdg_data_dirs_with_env(env: &dyn EnvSource) -> Vec<PathBuf> {
+    xdg_list_variable(env, XDG_DATA_DIRS).unwrap_or_else(default_xdg_data_dirs)
 }
 
 /// Read XDG_CONFIG_DIRS to the vector
 pub fn xdg_config_dirs() -> Vec<PathBuf> {
-    std::env::var(XDG_CONFIG_DIRS)
-        .map(|string| string.split(':').map(PathBuf::from).collect())
-        .unwrap_or(vec![PathBuf::from("/etc/xdg")])
+    xdg_config_dirs_with_env(&SystemEnv)
 }
 
-/// Error for some of the [`Xdg`] trait methods
-#[derive(Debug, Eq, PartialEq)]
-pub enum XdgError {
-    /// $HOME cannot be resolved and at least one of the XDG variables is missing
-    UnresolvedHomeDir,
+/// Read XDG_CONFIG_DIRS from the given [`EnvSource`] to the vector
+pub fn xdg_config_dirs_with_env(env: &dyn EnvSource) -> Vec<PathBuf> {
+    xdg_list_variable(env, XDG_CONFIG_DIRS).unwrap_or_else(default_xdg_config_dirs)
+}
+
+/// Find the first existing file with the given relative name in the search paths
+pub fn find_file(search_paths: &[PathBuf], name: impl AsRef<Path>) -> Option<PathBuf> {
+    search_paths
+        .iter()
+        .map(|p| p.join(name.as_ref()))
+        .find(|p| p.is_file())
+}
+
+/// Find all existing files matching the relative glob pattern (e.g. `themes/*.toml`) in the
+/// search paths. Files are returned in the order of the search paths.
+pub fn find_all_files(search_paths: &[PathBuf], pattern: &str) -> Vec<PathBuf> {
+    search_paths
+        .iter()
+        .filter_map(|p| {
+            let escaped = glob::Pattern::escape(&p.to_string_lossy());
+            glob::glob(&format!("{escaped}/{pattern}")).ok()
+        })
+        .flat_map(|paths| {
+            let mut paths: Vec<PathBuf> = paths.filter_map(Result::ok).collect();
+            paths.sort();
+            paths
+        })
+        .filter(|p| p.is_file())
+        .collect()
 }
 
 /// Retrive [`ProjectDirs`] and [`FullProjectDirs`] using XDG Base Directories standard
@@ -114,17 +191,71 @@ pub trait Xdg {
     /// stanard directories.
     fn xdg_with_env_exclude_missing(&self, env: XdgEnv) -> ProjectDirs;
 
-    /// Tries to retrive [`FullProjectDirs`] from XDG variables. May return error in case of
-    /// missing home fallback
-    fn xdg_with_env(&self, env: XdgEnv) -> Result<FullProjectDirs, XdgError>;
+    /// Tries to retrive [`FullProjectDirs`] from XDG variables. Returns
+    /// [`Error::MissingHomeDir`] if $HOME cannot be resolved (it is required for the fallbacks)
+    fn xdg_with_env(&self, env: XdgEnv) -> crate::Result<FullProjectDirs>;
 
     /// Retrive [`FullProjectDirs`] from XDG variables. Variables are resolved from the system.
-    fn xdg(&self) -> Result<FullProjectDirs, XdgError> {
+    fn xdg(&self) -> crate::Result<FullProjectDirs> {
         self.xdg_with_env(XdgEnv::new_system())
     }
+
+    /// Project config dirs in the order of precedence: `$XDG_CONFIG_HOME/<project-name>`
+    /// followed by `$XDG_CONFIG_DIRS/<project-name>`
+    fn xdg_config_search_paths_with_env(&self, env: XdgEnv) -> Vec<PathBuf>;
+
+    /// Project data dirs in the order of precedence: `$XDG_DATA_HOME/<project-name>`
+    /// followed by `$XDG_DATA_DIRS/<project-name>`
+    fn xdg_data_search_paths_with_env(&self, env: XdgEnv) -> Vec<PathBuf>;
+
+    /// Same as [`Xdg::xdg_config_search_paths_with_env`]. Variables are resolved from the system.
+    fn xdg_config_search_paths(&self) -> Vec<PathBuf> {
+        self.xdg_config_search_paths_with_env(XdgEnv::new_system())
+    }
+
+    /// Same as [`Xdg::xdg_data_search_paths_with_env`]. Variables are resolved from the system.
+    fn xdg_data_search_paths(&self) -> Vec<PathBuf> {
+        self.xdg_data_search_paths_with_env(XdgEnv::new_system())
+    }
+
+    /// Find the most important config file (e.g. `settings.toml`) in the
+    /// [`Xdg::xdg_config_search_paths`]
+    fn find_config_file(&self, name: &str) -> Option<PathBuf> {
+        find_file(&self.xdg_config_search_paths(), name)
+    }
+
+    /// Find all data files matching the glob pattern (e.g. `themes/*.toml`) in the
+    /// [`Xdg::xdg_data_search_paths`]. Most important files go first.
+    fn find_all_data_files(&self, pattern: &str) -> Vec<PathBuf> {
+        find_all_files(&self.xdg_data_search_paths(), pattern)
+    }
 }
 
 impl Xdg for Project {
+    fn xdg_config_search_paths_with_env(&self, env: XdgEnv) -> Vec<PathBuf> {
+        let config_home = env
+            .xdg_config_home
+            .or(env.home_dir.map(|p| p.join(".config")));
+
+        config_home
+            .into_iter()
+            .chain(env.xdg_config_dirs.unwrap_or_else(default_xdg_config_dirs))
+            .map(|p| p.join(&self.application_name))
+            .collect()
+    }
+
+    fn xdg_data_search_paths_with_env(&self, env: XdgEnv) -> Vec<PathBuf> {
+        let data_home = env
+            .xdg_data_home
+            .or(env.home_dir.map(|p| p.join(".local").join("share")));
+
+        data_home
+            .into_iter()
+            .chain(env.xdg_data_dirs.unwrap_or_else(default_xdg_data_dirs))
+            .map(|p| p.join(&self.application_name))
+            .collect()
+    }
+
     fn xdg_with_env_exclude_missing(&self, env: XdgEnv) -> ProjectDirs {
         let bin_dir = env.home_dir.map(|p| p.join(".local").join("bin"));
         let cache_dir = env.xdg_cache_home.map(|p| p.join(&self.application_name));
@@ -157,7 +288,7 @@ impl Xdg for Project {
         ProjectDirs::new(pd)
     }
 
-    fn xdg_with_env(&self, env: XdgEnv) -> Result<FullProjectDirs, XdgError> {
+    fn xdg_with_env(&self, env: XdgEnv) -> crate::Result<FullProjectDirs> {
         let home_dir = env.home_dir.clone();
         let mut pd = self.xdg_with_env_exclude_missing(env);
 
@@ -186,15 +317,10 @@ impl Xdg for Project {
             pd.0.entry(Directory::Data)
                 .or_insert(share_dir.join(&self.application_name));
 
-            pd.try_into().map_err(|e| {
-                eprintln!("Missing: {e:?}");
-                unreachable!(
-                    "XDG failed despite of having HOME set. This should never happen and IS A BUG."
-                )
-            })
+            pd.try_into().map_err(Error::MissingDirectories)
         } else {
             // bin_dir always depends on the home dir
-            Err(XdgError::UnresolvedHomeDir)
+            Err(Error::MissingHomeDir)
         }
     }
 }
@@ -0,0 +1,156 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime};
+
+use crate::dir_utils::{ProvisionPolicy, create_dir_with_mode};
+use crate::lock::process_alive;
+use crate::{Directory, Error, ProjectDirs, Result};
+
+/// Name of the temp area subdirectory, e.g. `/run/user/1000/my-app/temp`
+pub const TEMP_AREA: &str = "temp";
+
+/// Unique suffix of the temp directories created by this process
+static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Per-process temp directory created by the [`ProjectDirs::temp_dir`]. It is removed (with the
+/// content) on drop. Name starts with the PID of the owner, so directories left by the crashed
+/// processes are removed by the [`ProjectDirs::sweep_stale`]
+#[derive(Debug)]
+pub struct TempDir {
+    path: PathBuf,
+    keep: bool,
+}
+
+impl TempDir {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Disable the cleanup on drop and return the path
+    pub fn keep(mut self) -> PathBuf {
+        self.keep = true;
+        self.path.clone()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        if !self.keep {
+            let _ = std::fs::remove_dir_all(&self.path);
+        }
+    }
+}
+
+/// PID of the process that created the temp directory
+fn owner_pid(name: &str) -> Option<u32> {
+    name.split_once('-')?.0.parse().ok()
+}
+
+/// Whether anything in the tree (without following symlinks) was modified within `max_age`.
+/// Writes to the existing files do not change the modification time of their directory
+fn modified_within(path: &Path, max_age: Duration) -> std::io::Result<bool> {
+    let metadata = std::fs::symlink_metadata(path)?;
+    if metadata.modified()?.elapsed().unwrap_or(Duration::ZERO) < max_age {
+        return Ok(true);
+    }
+
+    if metadata.is_dir() {
+        for entry in std::fs::read_dir(path)? {
+            match modified_within(&entry?.path(), max_age) {
+                Ok(false) => {}
+                // Removed meanwhile
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                other => return other,
+            }
+        }
+    }
+    Ok(false)
+}
+
+impl ProjectDirs {
+    /// Temp area ([`TEMP_AREA`]) in the [`Directory::Runtime`], or in the [`Directory::Cache`]
+    /// when the runtime is not available
+    pub fn temp_area(&self) -> Option<PathBuf> {
+        self.temp_area_with_kind().map(|(_, path)| path)
+    }
+
+    fn temp_area_with_kind(&self) -> Option<(Directory, PathBuf)> {
+        self.runtime_or(Directory::Cache)
+            .map(|(dir, path)| (dir, path.join(TEMP_AREA)))
+    }
+
+    /// Create uniquely named [`TempDir`] in the [`ProjectDirs::temp_area`]. Missing directories
+    /// are created with the policy mode of the runtime (or cache) directory
+    pub fn temp_dir(&self, policy: &ProvisionPolicy) -> Result<TempDir> {
+        let (kind, area) = self
+            .temp_area_with_kind()
+            .ok_or(Error::MissingDirectories(vec![
+                Directory::Runtime,
+                Directory::Cache,
+            ]))?;
+        let mode = policy.mode(&kind);
+
+        if !area.is_dir() {
+            create_dir_with_mode(&area, mode)?;
+        }
+
+        loop {
+            let nanos = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default()
+                .subsec_nanos();
+            let name = format!(
+                "{}-{nanos:08x}-{}",
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed)
+            );
+            let path = area.join(name);
+
+            // Fails if the directory exists, so it is never shared with another process
+            match std::fs::create_dir(&path) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e.into()),
+            }
+            // Sets the mode explicitly, as umask applies to `create_dir`
+            create_dir_with_mode(&path, mode)?;
+            return Ok(TempDir { path, keep: false });
+        }
+    }
+
+    /// Remove temp directories of the dead processes (e.g. after a crash) with nothing inside
+    /// modified for at least `max_age`. Call it on startup. Directories of the running processes
+    /// (and of all processes where the liveness cannot be checked) are left untouched, as well as
+    /// the ones that cannot be read or removed. Returns the removed paths
+    pub fn sweep_stale(&self, max_age: Duration) -> Result<Vec<PathBuf>> {
+        let Some(area) = self.temp_area() else {
+            return Ok(Vec::new());
+        };
+        let entries = match std::fs::read_dir(&area) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut removed = Vec::new();
+        for entry in entries.flatten() {
+            let Some(pid) = entry.file_name().to_str().and_then(owner_pid) else {
+                continue;
+            };
+            if pid == std::process::id() || process_alive(pid) {
+                continue;
+            }
+
+            // Recently used, already removed (e.g. by the other sweeper) or unreadable
+            if !matches!(modified_within(&entry.path(), max_age), Ok(false)) {
+                continue;
+            }
+
+            if std::fs::remove_dir_all(entry.path()).is_ok() {
+                removed.push(entry.path());
+            }
+        }
+        removed.sort();
+        Ok(removed)
+    }
+}
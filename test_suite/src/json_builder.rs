@@ -1,4 +1,3 @@
-use crate::ENV;
 use serde_json::json;
 use serde_json_assert::{self as sja, assert_json_matches_no_panic};
 use std::{collections::HashMap, path::PathBuf};
@@ -102,11 +101,18 @@ fn test_single_input_output(
     name: String,
     input_file: PathBuf,
     output_file: PathBuf,
+    envs: HashMap<String, Option<String>>,
     override_project_root: bool,
 ) -> bool {
     let mut builder: project_dirs_builder::Builder =
         serde_json::from_reader(std::fs::File::open(input_file).unwrap()).unwrap();
 
+    // Variables of the env file stand for the system env, so the `custom_env` of the input
+    // takes precedence over them
+    for (key, value) in envs {
+        builder.custom_env.env.entry(key).or_insert(value);
+    }
+
     let mut project_root_should_be_overriden = false;
 
     if override_project_root {
@@ -209,32 +215,40 @@ fn test_builder() {
                 }
             }
 
-            let change_dir = match envs.get("__ROOT_CHDIR__") {
-                Some(Some(path)) => {
-                    if path.starts_with("/PROJECT_ROOT") {
-                        Some(
-                            get_project_root()
-                                .unwrap()
-                                .join(path.strip_prefix("/PROJECT_ROOT").unwrap()),
-                        )
-                    } else {
-                        panic!("You need to explicitly use /PROJECT_ROOT in __ROOT_CHDIR__");
-                    }
-                }
-                _ => None,
-            };
-
-            ENV.lock().unwrap().with_env(envs, change_dir, || {
-                test_single_input_output(
-                    test.0,
-                    test.1.input_file,
-                    test.1.output_file,
-                    std::env::var("__RESOLVE_PROJECT_ROOT__")
-                        .is_ok_and(|v| v == "true" || v == "1"),
-                )
-            })
+            // Working directory is shared by the parallel tests, so it is checked, not changed
+            if let Some(Some(path)) = envs.get("__ROOT_CHDIR__") {
+                let path = path
+                    .strip_prefix("/PROJECT_ROOT")
+                    .expect("You need to explicitly use /PROJECT_ROOT in __ROOT_CHDIR__");
+                assert_eq!(
+                    std::env::current_dir().unwrap(),
+                    get_project_root()
+                        .unwrap()
+                        .join(path.trim_start_matches('/')),
+                    "{} expects to be run from __ROOT_CHDIR__",
+                    test.0
+                );
+            }
+
+            let resolve_project_root = envs
+                .get("__RESOLVE_PROJECT_ROOT__")
+                .is_some_and(|v| v.as_deref() == Some("true") || v.as_deref() == Some("1"));
+
+            test_single_input_output(
+                test.0,
+                test.1.input_file,
+                test.1.output_file,
+                envs,
+                resolve_project_root,
+            )
         } else {
-            test_single_input_output(test.0, test.1.input_file, test.1.output_file, false)
+            test_single_input_output(
+                test.0,
+                test.1.input_file,
+                test.1.output_file,
+                HashMap::new(),
+                false,
+            )
         };
 
         if !was_ok {
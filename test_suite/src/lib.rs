@@ -1,24 +1,12 @@
 #![cfg(test)]
-pub mod env_mock;
 pub mod json_builder;
 pub mod utils;
 
-use std::{
-    collections::HashMap,
-    path::PathBuf,
-    sync::{LazyLock, Mutex},
-};
+use std::{collections::HashMap, path::PathBuf};
 
-use env_mock::EnvMock;
 use project_dirs::{Directory, FullProjectDirs, Project, ProjectDirs};
 use utils::*;
 
-fn create_env_mock() -> Mutex<EnvMock> {
-    Mutex::new(EnvMock::begin())
-}
-
-static ENV: LazyLock<Mutex<EnvMock>> = LazyLock::new(create_env_mock);
-
 #[test]
 fn project_name_unix() {
     for (orig, expected) in [
@@ -46,6 +34,57 @@ fn project_name_windows() {
     }
 }
 
+#[test]
+fn project_name_modes() {
+    use project_dirs::{NameCollision, NamingMode, find_name_collisions};
+
+    for (orig, transliterated, unicode) in [
+        ("żołądź project", "zoladz-project", "żołądź-project"),
+        ("Straße Müller", "strasse-muller", "straße-müller"),
+        ("Łódź_App", "lodz-app", "łódź-app"),
+        ("日本 app", "-app", "日本-app"),
+    ] {
+        let project = Project::new(Q, O, orig);
+        assert_eq!(
+            project
+                .with_naming_mode(NamingMode::Transliterate)
+                .application_name_unix(),
+            transliterated
+        );
+        let project = Project::new(Q, O, orig).with_naming_mode(NamingMode::Unicode);
+        assert_eq!(project.application_name_unix(), unicode);
+    }
+
+    assert_eq!(
+        Project::new(Q, O, "Żółć Straße")
+            .with_naming_mode(NamingMode::Transliterate)
+            .application_name_windows(),
+        "Zolc Strasse"
+    );
+
+    let collisions = find_name_collisions(
+        ["Über App", "uber-app", "Uber_App", "other", "other"],
+        NamingMode::Transliterate,
+    );
+    assert_eq!(
+        collisions,
+        vec![NameCollision {
+            cleaned: project_dirs::Project::new(Q, O, "uber-app")
+                .application_name()
+                .to_string(),
+            names: vec![
+                "Uber_App".to_string(),
+                "uber-app".to_string(),
+                "Über App".to_string()
+            ],
+        }]
+    );
+
+    let project = Project::new(Q, O, "Müller").with_naming_mode(NamingMode::Transliterate);
+    assert!(project.collides_with(&Project::new(Q, O, "muller")));
+    assert!(!project.collides_with(&Project::new(Q, O, "Müller")));
+}
+
 fn any_parent_is(path: &std::path::Path, expected_parent: &std::path::Path) -> bool {
     let mut is_in_target = false;
     let mut maybe_dir = Some(path);
@@ -89,6 +128,8 @@ fn unix_env_checks() {
         xdg_state_home: default_pb.clone(),
         xdg_cache_home: default_pb.clone(),
         xdg_runtime_dir: None,
+        xdg_data_dirs: None,
+        xdg_config_dirs: None,
     };
 
     assert!(env.is_ok());
@@ -111,24 +152,17 @@ fn unix_env_checks() {
 
 #[test]
 fn unix_pwd() {
+    use project_dirs::env::MemoryEnv;
     use project_dirs::strategy::unix::Unix as _;
     let project_root = get_project_root().unwrap();
-
-    let mut env = ENV.lock().unwrap();
-    env.chdir(&project_root);
-
-    // Make cure we've changed pwd
-    let pwd = std::env::current_dir().unwrap();
-    assert_eq!(pwd, project_root);
+    let env = MemoryEnv::new().with_current_dir(&project_root);
 
     let project = Project::new(Q, O, A);
-    let dirs: project_dirs::ProjectDirs = project.unix_pwd().unwrap().into();
+    let dirs: project_dirs::ProjectDirs = project.unix_pwd_with_env(&env).unwrap().into();
 
     for (_, dir) in dirs.0 {
         assert!(any_parent_is(&dir, &project_root));
     }
-
-    env.restore();
 }
 
 #[test]
@@ -231,63 +265,64 @@ fn triplet() {
 #[test]
 #[cfg(all(target_family = "unix", not(target_os = "macos")))]
 fn unix_systems_project_dirs() {
+    use project_dirs::env::MemoryEnv;
     use project_dirs::strategy::fhs::Fhs as _;
     use project_dirs::strategy::unix::Unix as _;
-    use project_dirs::strategy::xdg::Xdg as _;
+    use project_dirs::strategy::xdg::{Xdg as _, XdgEnv};
 
     let project = Project::new(Q, O, A);
-    {
-        let _env = ENV.lock().unwrap();
-        let dirs = project.project_dirs();
-        assert_eq!(dirs.system, project.fhs().into());
-        assert_eq!(dirs.local, project.unix_pwd().unwrap().into()); // race condition in pwd
-        assert_eq!(dirs.user, project.xdg().unwrap().into()); // race condition in user
-    }
+    let env = MemoryEnv::new()
+        .with_home_dir("/home/me")
+        .with_current_dir("/srv/work")
+        .with_var("XDG_RUNTIME_DIR", "/run/user/1000");
+
+    let dirs = project.project_dirs_with_env(&env);
+    #[cfg(not(target_os = "freebsd"))]
+    assert_eq!(dirs.system, project.fhs().into());
+    #[cfg(target_os = "freebsd")]
+    assert_eq!(
+        dirs.system,
+        project_dirs::strategy::bsd::Hier::hier(&project).into()
+    );
+    assert_eq!(dirs.local, project.unix_pwd_with_env(&env).unwrap().into());
+    assert_eq!(
+        dirs.user,
+        project
+            .xdg_with_env(XdgEnv::from_source(&env))
+            .unwrap()
+            .into()
+    );
 }
 
 #[test]
 fn xdg_special_dirs() {
-    use crate::env_mock::NONE_CHDIR;
+    use project_dirs::env::MemoryEnv;
     use project_dirs::strategy::xdg::*;
-    {
-        let mut env = ENV.lock().unwrap();
-        env.with_env(
-            vec![
-                ("XDG_DATA_DIRS".to_string(), Some("/a/b:/e/f".to_string())),
-                ("XDG_CONFIG_DIRS".to_string(), Some("/x/y:/z".to_string())),
-            ],
-            NONE_CHDIR,
-            || {
-                assert_eq!(
-                    xdg_data_dirs(),
-                    vec![PathBuf::from("/a/b"), PathBuf::from("/e/f")]
-                );
-
-                assert_eq!(
-                    xdg_config_dirs(),
-                    vec![PathBuf::from("/x/y"), PathBuf::from("/z")]
-                );
-            },
-        );
 
-        env.with_env(
-            vec![
-                ("XDG_DATA_DIRS".to_string(), None),
-                ("XDG_CONFIG_DIRS".to_string(), None),
-            ],
-            NONE_CHDIR,
-            || {
-                assert_eq!(xdg_config_dirs(), vec![PathBuf::from("/etc/xdg")]);
-                assert_eq!(
-                    xdg_data_dirs(),
-                    vec![
-                        PathBuf::from("/usr/local/share"),
-                        PathBuf::from("/usr/share")
-                    ]
-                );
-            },
-        );
-    }
+    let env = MemoryEnv::new()
+        .with_var("XDG_DATA_DIRS", "/a/b:/e/f")
+        .with_var("XDG_CONFIG_DIRS", "/x/y:/z");
+    assert_eq!(
+        xdg_data_dirs_with_env(&env),
+        vec![PathBuf::from("/a/b"), PathBuf::from("/e/f")]
+    );
+    assert_eq!(
+        xdg_config_dirs_with_env(&env),
+        vec![PathBuf::from("/x/y"), PathBuf::from("/z")]
+    );
+
+    let env = MemoryEnv::new();
+    assert_eq!(
+        xdg_config_dirs_with_env(&env),
+        vec![PathBuf::from("/etc/xdg")]
+    );
+    assert_eq!(
+        xdg_data_dirs_with_env(&env),
+        vec![
+            PathBuf::from("/usr/local/share"),
+            PathBuf::from("/usr/share")
+        ]
+    );
 }
 
 #[test]
@@ -300,6 +335,11 @@ fn builder_system_defaults() {
         organization: O.to_string(),
         application: A.to_string(),
         custom_env: CustomEnv::default(),
+        custom_directories: Vec::new(),
+        version: None,
+        naming_mode: Default::default(),
+        instance: None,
+        ownership: None,
     };
 
     let built = builder.build();
@@ -313,6 +353,7 @@ fn builder_system_defaults() {
                     directories: Vec::new(),
                     filter: None,
                     mountpoint: None,
+                    env_overrides: None,
                 },
             ),
             (
@@ -322,6 +363,7 @@ fn builder_system_defaults() {
                     directories: Vec::new(),
                     filter: None,
                     mountpoint: None,
+                    env_overrides: None,
                 },
             ),
             (
@@ -331,6 +373,7 @@ fn builder_system_defaults() {
                     directories: Vec::new(),
                     filter: None,
                     mountpoint: None,
+                    env_overrides: None,
                 },
             ),
         ])),
@@ -338,6 +381,11 @@ fn builder_system_defaults() {
         organization: O.to_string(),
         application: A.to_string(),
         custom_env: CustomEnv::default(),
+        custom_directories: Vec::new(),
+        version: None,
+        naming_mode: Default::default(),
+        instance: None,
+        ownership: None,
     };
 
     let built_splitted = builder_splitted.build();
@@ -347,3 +395,1730 @@ fn builder_system_defaults() {
     assert!(built.dirs.get("user").is_some());
     assert!(built.dirs.get("local").is_some());
 }
+
+#[test]
+fn macos_with_env() {
+    use project_dirs::strategy::macos::{Macos as _, MacosEnv};
+
+    let project = Project::new(Q, O, A);
+    assert!(project.macos_user_with_env(MacosEnv::default()).is_err());
+
+    let user = project
+        .macos_user_with_env(MacosEnv {
+            home_dir: Some(PathBuf::from("/Users/me")),
+        })
+        .unwrap();
+
+    let app_support = PathBuf::from("/Users/me/Library/Application Support")
+        .join("com.my-org-corp.funny-bunny-v-2137-0");
+
+    assert_eq!(user.config, app_support.join("config"));
+    assert_eq!(user.data, app_support.join("data"));
+    assert_eq!(
+        user.cache,
+        PathBuf::from("/Users/me/Library/Caches/com.my-org-corp.funny-bunny-v-2137-0")
+    );
+    assert_eq!(
+        user.log,
+        PathBuf::from("/Users/me/Library/Logs/com.my-org-corp.funny-bunny-v-2137-0")
+    );
+    assert_eq!(user.runtime, None);
+
+    let system = project.macos_system();
+    assert_eq!(
+        system.config,
+        PathBuf::from("/Library/Application Support/com.my-org-corp.funny-bunny-v-2137-0/config")
+    );
+    assert_eq!(
+        system.runtime,
+        Some(PathBuf::from(
+            "/var/run/com.my-org-corp.funny-bunny-v-2137-0"
+        ))
+    );
+}
+
+#[test]
+#[cfg(not(windows))]
+fn bsd_hier_localbase() {
+    use project_dirs::strategy::bsd::Hier as _;
+
+    let project = Project::new(Q, O, A);
+    let dirs = project.hier_with_localbase(std::path::Path::new("/opt/local"));
+
+    assert_eq!(dirs.bin, PathBuf::from("/opt/local/bin"));
+    assert_eq!(
+        dirs.config,
+        PathBuf::from("/opt/local/etc/funny-bunny-v-2137-0")
+    );
+    assert_eq!(dirs.data, PathBuf::from("/var/db/funny-bunny-v-2137-0"));
+    assert_eq!(project.hier().bin, PathBuf::from("/usr/local/bin"));
+}
+
+#[test]
+fn provision_dirs() {
+    use project_dirs::dir_utils::{Provision as _, ProvisionPolicy};
+
+    let root = ScratchDir::new("provision");
+
+    let dirs = project_dirs::strategy::unix::unix(&root.join("app"));
+    std::fs::create_dir_all(&dirs.cache).unwrap();
+    std::fs::write(&dirs.log, "").unwrap();
+
+    let policy = ProvisionPolicy::default().with_mode(Directory::Data, 0o750);
+    let report = dirs.provision(&policy);
+
+    assert_eq!(report.existing.get(&Directory::Cache), Some(&dirs.cache));
+    assert_eq!(report.non_dirs.get(&Directory::Log), Some(&dirs.log));
+    assert!(report.denied.0.is_empty());
+    // Project root (and config) is a parent of the cache
+    assert_eq!(report.existing.get(&Directory::Config), Some(&dirs.config));
+    assert_eq!(report.created.get(&Directory::Data), Some(&dirs.data));
+    assert!(dirs.data.is_dir());
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = |p: &std::path::Path| std::fs::metadata(p).unwrap().permissions().mode() & 0o777;
+
+        assert_eq!(mode(dirs.runtime.as_ref().unwrap()), 0o700);
+        assert_eq!(mode(&dirs.data), 0o750);
+        assert_eq!(mode(&dirs.state), 0o755);
+    }
+
+    // Second run does not create anything
+    let report = dirs.provision(&policy);
+    assert!(report.created.0.is_empty());
+}
+
+#[test]
+fn provision_fresh_parent_mode() {
+    use project_dirs::dir_utils::{Provision as _, ProvisionPolicy};
+
+    let root = ScratchDir::new("provision-fresh");
+
+    // Config (and project root) is the parent of all other directories
+    let dirs = project_dirs::strategy::unix::unix(&root.join("app"));
+    let policy = ProvisionPolicy::default().with_mode(Directory::Config, 0o700);
+    let report = dirs.provision(&policy);
+
+    assert_eq!(report.created.get(&Directory::Config), Some(&dirs.config));
+    assert_eq!(
+        report.created.get(&Directory::ProjectRoot),
+        dirs.project_root.as_ref()
+    );
+    assert!(report.existing.0.is_empty());
+    assert_eq!(report.created.0.len(), 10);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = |p: &std::path::Path| std::fs::metadata(p).unwrap().permissions().mode() & 0o777;
+
+        assert_eq!(mode(&dirs.config), 0o700);
+        assert_eq!(mode(&dirs.bin), 0o755);
+    }
+}
+
+#[test]
+fn xdg_search_paths() {
+    use project_dirs::strategy::xdg::*;
+
+    let project = Project::new(Q, O, A);
+    let mut env = XdgEnv {
+        home_dir: Some(PathBuf::from("/home/me")),
+        ..Default::default()
+    };
+
+    assert_eq!(
+        project.xdg_config_search_paths_with_env(env.clone()),
+        vec![
+            PathBuf::from("/home/me/.config/funny-bunny-v-2137-0"),
+            PathBuf::from("/etc/xdg/funny-bunny-v-2137-0"),
+        ]
+    );
+
+    env.extend_with_env(
+        [
+            (XDG_CONFIG_HOME, Some("/cfg")),
+            (XDG_CONFIG_DIRS, Some("/x/y:relative:/z")),
+            (XDG_DATA_DIRS, Some("/a/b")),
+        ]
+        .into_iter(),
+        false,
+    );
+
+    assert_eq!(
+        project.xdg_config_search_paths_with_env(env.clone()),
+        vec![
+            PathBuf::from("/cfg/funny-bunny-v-2137-0"),
+            PathBuf::from("/x/y/funny-bunny-v-2137-0"),
+            PathBuf::from("/z/funny-bunny-v-2137-0"),
+        ]
+    );
+    assert_eq!(
+        project.xdg_data_search_paths_with_env(env),
+        vec![
+            PathBuf::from("/home/me/.local/share/funny-bunny-v-2137-0"),
+            PathBuf::from("/a/b/funny-bunny-v-2137-0"),
+        ]
+    );
+}
+
+#[test]
+fn xdg_find_files() {
+    use project_dirs::strategy::xdg::{find_all_files, find_file};
+
+    let root = ScratchDir::new("find");
+    let (first, second) = (root.join("first"), root.join("second"));
+    std::fs::create_dir_all(first.join("themes")).unwrap();
+    std::fs::create_dir_all(second.join("themes")).unwrap();
+    std::fs::write(second.join("settings.toml"), "").unwrap();
+    std::fs::write(first.join("themes").join("dark.toml"), "").unwrap();
+    std::fs::write(second.join("themes").join("dark.toml"), "").unwrap();
+    std::fs::write(second.join("themes").join("light.toml"), "").unwrap();
+    std::fs::write(second.join("themes").join("notes.txt"), "").unwrap();
+
+    let search_paths = vec![first.clone(), second.clone()];
+    assert_eq!(
+        find_file(&search_paths, "settings.toml"),
+        Some(second.join("settings.toml"))
+    );
+    assert_eq!(find_file(&search_paths, "missing.toml"), None);
+    assert_eq!(
+        find_all_files(&search_paths, "themes/*.toml"),
+        vec![
+            first.join("themes").join("dark.toml"),
+            second.join("themes").join("dark.toml"),
+            second.join("themes").join("light.toml"),
+        ]
+    );
+}
+
+#[test]
+fn scoped_resolve_file() {
+    use project_dirs::{Scope, Scoped};
+
+    let root = ScratchDir::new("scoped");
+    let scope_dir = |name: &str| {
+        let path = root.join(name);
+        std::fs::create_dir_all(&path).unwrap();
+        ProjectDirs::new(HashMap::from([(Directory::Config, path)]))
+    };
+
+    let scoped = Scoped {
+        user: scope_dir("user"),
+        system: scope_dir("system"),
+        local: scope_dir("local"),
+    };
+    std::fs::write(root.join("system").join("config.toml"), "").unwrap();
+    std::fs::write(root.join("user").join("config.toml"), "").unwrap();
+
+    let resolved = scoped.resolve_file("config.toml", Directory::Config);
+    assert_eq!(
+        resolved.candidates,
+        vec![
+            (Scope::User, root.join("user").join("config.toml")),
+            (Scope::System, root.join("system").join("config.toml")),
+        ]
+    );
+    assert_eq!(
+        resolved.effective(),
+        Some(&root.join("user").join("config.toml"))
+    );
+
+    let resolved = scoped.resolve_file_with_precedence(
+        "config.toml",
+        Directory::Config,
+        &[Scope::System, Scope::User],
+    );
+    assert_eq!(
+        resolved.effective(),
+        Some(&root.join("system").join("config.toml"))
+    );
+
+    let resolved = scoped.resolve_file("config.toml", Directory::Data);
+    assert!(resolved.candidates.is_empty());
+    assert_eq!(resolved.effective(), None);
+}
+
+#[test]
+fn custom_directories() {
+    use project_dirs::CustomDirectory;
+    use project_dirs::strategy::unix::unix;
+
+    let project = Project::new(Q, O, A)
+        .with_custom_directory(CustomDirectory::new("plugins", Directory::Data, "plugins").unwrap())
+        .with_custom_directory(CustomDirectory::new("sockets", Directory::Runtime, "sock").unwrap())
+        .with_custom_directory(
+            CustomDirectory::new(
+                "plugin-cache",
+                Directory::Custom("plugins".to_string()),
+                "cache",
+            )
+            .unwrap(),
+        );
+
+    let dirs = ProjectDirs::from(unix(&PathBuf::from("/opt/app")))
+        .with_custom_directories(project.custom_directories());
+
+    let plugins = Directory::Custom("plugins".to_string());
+    assert_eq!(
+        dirs.get(&plugins),
+        Some(&PathBuf::from("/opt/app/data/plugins"))
+    );
+    assert_eq!(
+        dirs.get(&Directory::Custom("plugin-cache".to_string())),
+        Some(&PathBuf::from("/opt/app/data/plugins/cache"))
+    );
+
+    // Base is missing, so the custom directory is skipped
+    let dirs = ProjectDirs::new(HashMap::from([(Directory::Data, "data".into())]))
+        .with_custom_directories(project.custom_directories());
+    assert!(
+        dirs.get(&Directory::Custom("sockets".to_string()))
+            .is_none()
+    );
+
+    let scoped = project.project_dirs();
+    if let Some(data) = scoped.user.get(&Directory::Data) {
+        assert_eq!(scoped.user.get(&plugins), Some(&data.join("plugins")));
+    }
+
+    // Custom directories are plain strings in serde
+    let directories: Vec<Directory> = serde_json::from_str(r#"["data", "plugins"]"#).unwrap();
+    assert_eq!(directories, vec![Directory::Data, plugins.clone()]);
+    assert_eq!(serde_json::to_string(&plugins).unwrap(), r#""plugins""#);
+
+    // Built-in names and other charsets are rejected
+    for name in [
+        "data",
+        "project-root",
+        "Plugins",
+        "my_plugins",
+        "-plugins",
+        "",
+    ] {
+        assert!(matches!(
+            CustomDirectory::new(name, Directory::Data, "x"),
+            Err(project_dirs::Error::InvalidCustomDirectory { .. })
+        ));
+    }
+
+    // Subpath cannot replace the base or climb out of it
+    for subpath in ["/etc", "../escape", "plugins/../../escape"] {
+        assert!(matches!(
+            CustomDirectory::new("plugins", Directory::Data, subpath),
+            Err(project_dirs::Error::InvalidCustomDirectory { .. })
+        ));
+    }
+    assert!(CustomDirectory::new("plugins", Directory::Data, "./plugins/v1").is_ok());
+
+    // Manifests may use only declared custom directories, so typos are not taken as custom
+    let manifest = |directories: &[&str]| {
+        serde_json::from_value::<project_dirs_builder::Builder>(serde_json::json!({
+            "qualifier": Q,
+            "organization": O,
+            "application": A,
+            "custom_directories": [{"name": "plugins", "base": "data", "subpath": "plugins"}],
+            "spec": {"user": {"strategy": "xdg", "directories": directories}}
+        }))
+    };
+    assert!(manifest(&["config", "plugins"]).is_ok());
+    let error = manifest(&["confg"]).unwrap_err().to_string();
+    assert!(error.contains("\"confg\""), "{error}");
+}
+
+#[test]
+fn template_errors() {
+    use project_dirs_builder::Template;
+
+    let project = Project::new(Q, O, A);
+    let templates = HashMap::from([
+        (Directory::Lib, "{dir:include}/lib".to_string()),
+        (Directory::Include, "{dir:lib}/include".to_string()),
+        (Directory::Cache, "{dir:plugins}/cache".to_string()),
+        (
+            Directory::Custom("plugins".to_string()),
+            "{dir:plugins}".to_string(),
+        ),
+        (Directory::Log, "{MISSING}/log".to_string()),
+        (Directory::State, "{HOME/state".to_string()),
+    ]);
+    let template = Template::new(&project, &templates, |_| None);
+    let message = |dir: Directory| template.render_dir(&dir).unwrap_err().to_string();
+
+    assert_eq!(
+        message(Directory::Lib),
+        "Template of the lib directory references itself"
+    );
+    assert_eq!(
+        message(Directory::Cache),
+        "Template of the plugins directory references itself"
+    );
+    assert_eq!(
+        message(Directory::Log),
+        "Cannot resolve template placeholder: MISSING"
+    );
+    assert_eq!(
+        message(Directory::State),
+        "Unclosed template placeholder: {HOME/state"
+    );
+    assert_eq!(
+        message(Directory::Data),
+        "Cannot resolve template placeholder: dir:data"
+    );
+    assert_eq!(
+        template.render("{dir:bin}").unwrap_err().to_string(),
+        "Cannot resolve template placeholder: dir:bin"
+    );
+}
+
+#[test]
+fn memory_env_source() {
+    use project_dirs::env::{EnvSource, MemoryEnv};
+    use project_dirs::strategy::unix::Unix as _;
+    use project_dirs::strategy::xdg::*;
+
+    let env = MemoryEnv::new()
+        .with_var(HOME, "/home/me")
+        .with_var(XDG_DATA_DIRS, "/a/b:/e/f")
+        .with_current_dir("/work")
+        .with_uid(1000);
+
+    assert_eq!(env.home_dir(), Some(PathBuf::from("/home/me")));
+    assert_eq!(env.uid(), Some(1000));
+    assert!(env.current_exe().is_err());
+    assert_eq!(
+        xdg_data_dirs_with_env(&env),
+        vec![PathBuf::from("/a/b"), PathBuf::from("/e/f")]
+    );
+    assert_eq!(
+        xdg_config_dirs_with_env(&env),
+        vec![PathBuf::from("/etc/xdg")]
+    );
+
+    let project = Project::new(Q, O, A);
+    assert_eq!(
+        project.unix_pwd_with_env(&env).unwrap().config,
+        PathBuf::from("/work/.funny-bunny-v-2137-0")
+    );
+    assert_eq!(
+        project.unix_home_with_env(&env).unwrap().config,
+        PathBuf::from("/home/me/.funny-bunny-v-2137-0")
+    );
+    assert!(matches!(
+        project.unix_binary_with_env(&env),
+        Err(project_dirs::Error::UnreadableCurrentExe(_))
+    ));
+
+    let empty = MemoryEnv::new();
+    let error = project.unix_pwd_with_env(&empty).unwrap_err();
+    assert!(matches!(
+        error,
+        project_dirs::Error::UnreadableCurrentDir(_)
+    ));
+    // Cause is available as the source, not repeated in the message
+    assert_eq!(
+        error.to_string(),
+        "Current working directory cannot be read"
+    );
+    assert!(std::error::Error::source(&error).is_some());
+    assert!(matches!(
+        project.xdg_with_env(XdgEnv::from_source(&empty)),
+        Err(project_dirs::Error::MissingHomeDir)
+    ));
+}
+
+#[test]
+#[cfg(all(target_family = "unix", not(target_os = "macos")))]
+fn unix_project_dirs_with_env() {
+    use project_dirs::env::MemoryEnv;
+    use project_dirs::strategy::unix::Unix as _;
+    use project_dirs::strategy::xdg::{Xdg as _, XdgEnv};
+
+    let project = Project::new(Q, O, A);
+    let env = MemoryEnv::new()
+        .with_home_dir("/home/me")
+        .with_var("XDG_CONFIG_HOME", "/cfg")
+        .with_current_dir("/work");
+
+    let dirs = project.project_dirs_with_env(&env);
+    assert_eq!(
+        dirs.user,
+        project
+            .xdg_with_env(XdgEnv::from_source(&env))
+            .unwrap()
+            .into()
+    );
+    assert_eq!(
+        dirs.user.get(&Directory::Config),
+        Some(&PathBuf::from("/cfg/funny-bunny-v-2137-0"))
+    );
+    assert_eq!(dirs.local, project.unix_pwd_with_env(&env).unwrap().into());
+
+    // No cwd and no home
+    let dirs = project.project_dirs_with_env(&MemoryEnv::new());
+    assert!(dirs.user.0.is_empty());
+    assert!(dirs.local.0.is_empty());
+}
+
+#[test]
+fn strategy_registry() {
+    use project_dirs::Error;
+    use project_dirs::env::{EnvSource, MemoryEnv};
+    use project_dirs::strategy::StrategyRegistry;
+    use project_dirs::strategy::fhs::Fhs as _;
+    use project_dirs::strategy::unix::unix;
+    use project_dirs_builder::{Builder, CustomEnv, Spec, SpecEntry, Strategy};
+
+    let project = Project::new(Q, O, A);
+    let mut registry = StrategyRegistry::with_defaults();
+
+    assert_eq!(
+        registry
+            .resolve("fhs", &project, &MemoryEnv::new())
+            .unwrap(),
+        project.fhs().into()
+    );
+    assert!(
+        registry
+            .resolve("missing", &project, &MemoryEnv::new())
+            .is_err()
+    );
+
+    let previous = registry.register("company", |_: &Project, env: &dyn EnvSource| {
+        let root = env
+            .var("COMPANY_ROOT")
+            .ok_or(Error::custom("COMPANY_ROOT is not set"))?;
+        Ok(unix(&PathBuf::from(root)).into())
+    });
+    assert!(previous.is_none());
+    assert!(registry.names().any(|name| name == "company"));
+
+    let entry = |strategy| SpecEntry {
+        strategy,
+        directories: vec![Directory::Config],
+        filter: None,
+        mountpoint: None,
+        env_overrides: None,
+    };
+
+    let builder = Builder {
+        spec: Spec::Custom(HashMap::from([
+            (
+                "company".to_string(),
+                entry(Strategy::Registered("company".to_string())),
+            ),
+            (
+                "unknown".to_string(),
+                entry(Strategy::Registered("unknown".to_string())),
+            ),
+        ])),
+        qualifier: Q.to_string(),
+        organization: O.to_string(),
+        application: A.to_string(),
+        custom_env: CustomEnv {
+            env: HashMap::from([("COMPANY_ROOT".to_string(), Some("/srv/company".to_string()))]),
+            fallback_to_system: false,
+            allow_variable_clearing: false,
+        },
+        custom_directories: Vec::new(),
+        version: None,
+        naming_mode: Default::default(),
+        instance: None,
+        ownership: None,
+    };
+
+    let built = builder.build_with_registry(&registry);
+    assert_eq!(
+        built.dirs["company"].get(&Directory::Config),
+        Some(&PathBuf::from("/srv/company"))
+    );
+    assert!(built.dirs["unknown"].0.is_empty());
+    assert_eq!(built.errors["unknown"], "Unknown strategy: unknown");
+    assert!(!built.errors.contains_key("company"));
+
+    // Custom strategies are not known to the default registry
+    assert!(builder.build().dirs["company"].0.is_empty());
+}
+
+#[test]
+fn versioned_dirs_migration() {
+    use project_dirs::migration::{MigrationRegistry, VERSION_STAMP};
+
+    let root = ScratchDir::new("migration");
+
+    let base: ProjectDirs = project_dirs::strategy::unix::unix(&root.join("app")).into();
+    let v1 = base.clone().with_version("1");
+    let v3 = base.clone().with_version("3");
+    assert_eq!(v1.get(&Directory::Data), Some(&root.join("app/data/v1")));
+    assert_eq!(v1.get(&Directory::Cache), base.get(&Directory::Cache));
+
+    let mut registry = MigrationRegistry::new();
+    registry.register_move("1", "2").register("2", "3", |step| {
+        let from = step.from.get(&Directory::Data).unwrap();
+        let to = step.to.get(&Directory::Data).unwrap();
+        std::fs::create_dir_all(to)?;
+        std::fs::copy(from.join("db"), to.join("db.v3"))?;
+        Ok(())
+    });
+
+    // Fresh installation
+    let plan = registry.plan(&base, "3").unwrap();
+    assert_eq!(plan.current_version, None);
+    assert!(plan.is_empty());
+
+    std::fs::create_dir_all(v1.get(&Directory::Data).unwrap()).unwrap();
+    std::fs::write(v1.get(&Directory::Data).unwrap().join("db"), "data").unwrap();
+    std::fs::write(root.join("app/data").join(VERSION_STAMP), "1\n").unwrap();
+
+    // Dry-run does not touch anything
+    let plan = registry.plan(&base, "3").unwrap();
+    assert_eq!(plan.current_version.as_deref(), Some("1"));
+    let versions: Vec<_> = plan
+        .steps
+        .iter()
+        .map(|s| (s.from_version.as_str(), s.to_version.as_str()))
+        .collect();
+    assert_eq!(versions, vec![("1", "2"), ("2", "3")]);
+    assert!(v1.get(&Directory::Data).unwrap().join("db").is_file());
+
+    assert!(matches!(
+        registry.plan(&base, "4"),
+        Err(project_dirs::Error::NoMigrationPath { .. })
+    ));
+
+    registry.migrate(&base, "3").unwrap();
+    assert_eq!(
+        std::fs::read_to_string(v3.get(&Directory::Data).unwrap().join("db.v3")).unwrap(),
+        "data"
+    );
+    assert!(!v1.get(&Directory::Data).unwrap().exists());
+    assert_eq!(
+        MigrationRegistry::current_version(&base)
+            .unwrap()
+            .as_deref(),
+        Some("3")
+    );
+    assert!(registry.plan(&base, "3").unwrap().is_empty());
+
+    // Project applies version before custom directories
+    let project = Project::new(Q, O, A)
+        .with_version("3")
+        .with_custom_directory(
+            project_dirs::CustomDirectory::new("plugins", Directory::Data, "plugins").unwrap(),
+        );
+    let dirs = project.finalize_project_dirs(base.clone());
+    assert_eq!(
+        dirs.get(&Directory::Custom("plugins".to_string())),
+        Some(&root.join("app/data/v3/plugins"))
+    );
+}
+
+#[test]
+#[cfg(all(target_family = "unix", not(target_os = "macos")))]
+fn current_strategies_versioned_once() {
+    let builder: project_dirs_builder::Builder = serde_json::from_value(serde_json::json!({
+        "qualifier": Q,
+        "organization": O,
+        "application": "App",
+        "version": "2",
+        "custom_env": {"env": {"HOME": "/home/me"}, "fallback_to_system": false},
+        "spec": {
+            "user": {"strategy": "current-user"},
+            "system": {"strategy": "current-system"}
+        }
+    }))
+    .unwrap();
+
+    let built = builder.build();
+    assert!(built.errors.is_empty(), "{:?}", built.errors);
+    let config = |name: &str| built.dirs[name].get(&Directory::Config).cloned();
+    assert_eq!(
+        config("user"),
+        Some(PathBuf::from("/home/me/.config/app/v2"))
+    );
+    assert_eq!(config("system"), Some(PathBuf::from("/etc/app/v2")));
+}
+
+#[test]
+#[cfg(all(target_family = "unix", not(target_os = "macos")))]
+fn current_strategies_instance_once() {
+    let builder: project_dirs_builder::Builder = serde_json::from_value(serde_json::json!({
+        "qualifier": Q,
+        "organization": O,
+        "application": "App",
+        "version": "2",
+        "instance": "blue",
+        "custom_env": {"env": {"HOME": "/home/me"}, "fallback_to_system": false},
+        "spec": {
+            "user": {"strategy": "current-user"},
+            "system": {"strategy": "current-system"}
+        }
+    }))
+    .unwrap();
+
+    let built = builder.build();
+    assert!(built.errors.is_empty(), "{:?}", built.errors);
+    let dir = |name: &str, dir: Directory| built.dirs[name].get(&dir).cloned();
+    assert_eq!(
+        dir("system", Directory::State),
+        Some(PathBuf::from("/var/lib/app/blue/v2"))
+    );
+    assert_eq!(
+        dir("system", Directory::Runtime),
+        Some(PathBuf::from("/run/app/blue"))
+    );
+    assert_eq!(
+        dir("user", Directory::State),
+        Some(PathBuf::from("/home/me/.local/state/app/blue/v2"))
+    );
+    // Config is shared between the instances
+    assert_eq!(
+        dir("user", Directory::Config),
+        Some(PathBuf::from("/home/me/.config/app/v2"))
+    );
+}
+
+#[test]
+fn unversioned_dirs_migration() {
+    use project_dirs::migration::{MigrationRegistry, UNVERSIONED, VERSION_STAMP};
+
+    let root = ScratchDir::new("unversioned");
+
+    let base: ProjectDirs = project_dirs::strategy::unix::unix(&root.join("app")).into();
+    let v1 = base.clone().with_version("1");
+    let v2 = base.clone().with_version("2");
+    for dir in base.0.values() {
+        std::fs::create_dir_all(dir).unwrap();
+    }
+
+    // Only nested project directories, nothing to migrate
+    let mut registry = MigrationRegistry::new();
+    assert_eq!(MigrationRegistry::current_version(&base).unwrap(), None);
+
+    // Data written before the application started to use versions
+    std::fs::write(root.join("app/data/db"), "data").unwrap();
+    std::fs::write(root.join("app/settings.toml"), "key = 1").unwrap();
+    assert_eq!(
+        MigrationRegistry::current_version(&base)
+            .unwrap()
+            .as_deref(),
+        Some(UNVERSIONED)
+    );
+    assert!(matches!(
+        registry.plan(&base, "2"),
+        Err(project_dirs::Error::NoMigrationPath { .. })
+    ));
+
+    registry
+        .register_move(UNVERSIONED, "1")
+        .register_move("1", "2");
+    registry.migrate(&base, "1").unwrap();
+    assert_eq!(
+        std::fs::read_to_string(v1.get(&Directory::Data).unwrap().join("db")).unwrap(),
+        "data"
+    );
+    assert!(
+        v1.get(&Directory::Config)
+            .unwrap()
+            .join("settings.toml")
+            .is_file()
+    );
+    assert!(!root.join("app/settings.toml").exists());
+    // Nested directories stay in place
+    assert!(root.join("app/data").is_dir());
+    assert!(root.join("app/state").is_dir());
+    assert!(!v1.get(&Directory::Config).unwrap().join("data").exists());
+
+    // Existing target is reported and the version is not advanced
+    std::fs::create_dir_all(v2.get(&Directory::Data).unwrap()).unwrap();
+    assert!(matches!(
+        registry.migrate(&base, "2"),
+        Err(project_dirs::Error::MigrationConflict { path })
+            if &path == v2.get(&Directory::Data).unwrap()
+    ));
+    assert!(v1.get(&Directory::Config).unwrap().is_dir());
+    assert_eq!(
+        std::fs::read_to_string(root.join("app/data").join(VERSION_STAMP)).unwrap(),
+        "1"
+    );
+
+    // Interrupted run: config was moved, data was not. Resuming moves the rest
+    std::fs::remove_dir(v2.get(&Directory::Data).unwrap()).unwrap();
+    std::fs::rename(
+        v1.get(&Directory::Config).unwrap(),
+        v2.get(&Directory::Config).unwrap(),
+    )
+    .unwrap();
+    registry.migrate(&base, "2").unwrap();
+    assert!(
+        v2.get(&Directory::Config)
+            .unwrap()
+            .join("settings.toml")
+            .is_file()
+    );
+    assert!(v2.get(&Directory::Data).unwrap().join("db").is_file());
+    assert!(!v1.get(&Directory::Data).unwrap().exists());
+
+    // Running it again is a no-op
+    let plan = registry.migrate(&base, "2").unwrap();
+    assert!(plan.steps.is_empty());
+    assert!(v2.get(&Directory::Data).unwrap().join("db").is_file());
+}
+
+#[test]
+fn relocate_legacy_layout() {
+    use project_dirs::migration::{RelocationPlan, RelocationStatus};
+
+    let root = ScratchDir::new("relocation");
+
+    let source: ProjectDirs = project_dirs::strategy::unix::unix(&root.join(".app")).into();
+    let target = ProjectDirs::new(HashMap::from([
+        (Directory::Config, root.join("config/app")),
+        (Directory::Data, root.join("share/app")),
+        (Directory::Cache, root.join("cache/app")),
+        (Directory::State, root.join("state/app")),
+        // Shared with other applications, like XDG `~/.local/bin`
+        (Directory::Bin, root.join("bin")),
+    ]));
+    let project = Project::new(Q, O, "app");
+
+    std::fs::create_dir_all(source.get(&Directory::Data).unwrap()).unwrap();
+    std::fs::create_dir_all(source.get(&Directory::Cache).unwrap()).unwrap();
+    std::fs::create_dir_all(source.get(&Directory::State).unwrap()).unwrap();
+    std::fs::write(root.join(".app/settings.toml"), "").unwrap();
+    std::fs::write(root.join(".app/data/db"), "").unwrap();
+    std::fs::create_dir_all(target.get(&Directory::State).unwrap()).unwrap();
+    std::fs::create_dir_all(source.get(&Directory::Bin).unwrap()).unwrap();
+    std::fs::create_dir_all(root.join("bin")).unwrap();
+    std::fs::write(root.join("bin/other-tool"), "").unwrap();
+
+    let plan = RelocationPlan::new(&project, &source, &target);
+    let status = |dir: Directory| {
+        plan.relocations
+            .iter()
+            .find(|r| r.directory == dir)
+            .map(|r| r.status)
+    };
+    assert_eq!(status(Directory::Data), Some(RelocationStatus::Move));
+    assert_eq!(status(Directory::State), Some(RelocationStatus::Conflict));
+    assert_eq!(status(Directory::Log), None);
+    assert_eq!(status(Directory::Bin), Some(RelocationStatus::Shared));
+    assert_eq!(plan.conflicts().count(), 1);
+    // Parent (config) is moved after the nested dirs
+    assert_eq!(
+        plan.relocations.last().unwrap().directory,
+        Directory::Config
+    );
+
+    let report = plan.execute(cfg!(unix));
+    assert!(report.has_problems());
+    assert_eq!(
+        report.conflicts.get(&Directory::State),
+        Some(&root.join(".app/state"))
+    );
+    assert!(root.join("share/app/db").is_file());
+    assert!(root.join("cache/app").is_dir());
+    // Parent of the conflicting state is left in place
+    assert_eq!(
+        report.conflicts.get(&Directory::Config),
+        Some(&root.join(".app"))
+    );
+    assert!(root.join(".app/settings.toml").is_file());
+
+    #[cfg(unix)]
+    {
+        assert_eq!(
+            report.symlinks.get(&Directory::Data),
+            Some(&root.join(".app/data"))
+        );
+        assert!(root.join(".app/data/db").is_file());
+    }
+
+    // Conflict resolved by the user
+    std::fs::remove_dir_all(root.join("state/app")).unwrap();
+    let report = RelocationPlan::new(&project, &source, &target).execute(false);
+    assert!(!report.has_problems());
+    assert!(root.join("config/app/settings.toml").is_file());
+    assert!(root.join("state/app").is_dir());
+    // Shared bin neither blocks the parent nor is touched
+    assert!(report.moved.get(&Directory::Bin).is_none());
+    assert_eq!(std::fs::read_dir(root.join("bin")).unwrap().count(), 1);
+
+    // Nothing left to move
+    let plan = RelocationPlan::new(&project, &source, &target);
+    assert!(
+        plan.relocations
+            .iter()
+            .all(|r| r.status != RelocationStatus::Move)
+    );
+}
+
+#[test]
+fn relocate_short_app_name() {
+    use project_dirs::migration::{RelocationPlan, RelocationStatus};
+
+    // `cal` is a substring of `.local`, but only whole components name the project
+    let project = Project::new(Q, O, "cal");
+    let home = PathBuf::from("/home/me");
+    let source = ProjectDirs::new(HashMap::from([
+        (Directory::Bin, home.join(".local/bin")),
+        (Directory::Data, home.join(".local/share/cal")),
+        (Directory::Config, home.join(".config/com.my-org.cal")),
+    ]));
+    let target = ProjectDirs::new(HashMap::from([
+        (Directory::Bin, home.join(".cal/bin")),
+        (Directory::Data, home.join(".cal/data")),
+        (Directory::Config, home.join(".cal")),
+    ]));
+
+    let plan = RelocationPlan::new(&project, &source, &target);
+    let status = |dir: Directory| {
+        plan.relocations
+            .iter()
+            .find(|r| r.directory == dir)
+            .map(|r| r.status)
+    };
+    assert_eq!(status(Directory::Bin), Some(RelocationStatus::Shared));
+    // Missing on the filesystem, but scoped to the project
+    assert_eq!(status(Directory::Data), Some(RelocationStatus::Missing));
+    assert_eq!(status(Directory::Config), Some(RelocationStatus::Missing));
+}
+
+#[test]
+fn project_id() {
+    use project_dirs::ProjectId;
+
+    let id = ProjectId::builder()
+        .application(A)
+        .organization(O)
+        .qualifier(Q)
+        .build()
+        .unwrap();
+
+    assert_eq!(id.reverse_dns(), "com.my-org-corp.funny-bunny-v-2137-0");
+    assert_eq!(id.dbus_name(), "com.my_org_corp.funny_bunny_v_2137_0");
+    assert_eq!(id.flatpak_app_id(), "com.my_org_corp.funny-bunny-v-2137-0");
+    assert_eq!(id.windows_path(), "my-org Corp\\funny-bunny v.2137_0");
+    assert_eq!(
+        id.project().application_name(),
+        Project::new(Q, O, A).application_name()
+    );
+
+    let id = ProjectId::builder()
+        .qualifier("io.github")
+        .organization("2bit")
+        .application("9 lives")
+        .build()
+        .unwrap();
+    assert_eq!(id.reverse_dns(), "io.github.2bit.9-lives");
+    assert_eq!(id.dbus_name(), "io.github._2bit._9_lives");
+    assert_eq!(id.flatpak_app_id(), "io.github._2bit._9-lives");
+
+    let invalid = |q: &str, o: &str, a: &str| match ProjectId::builder()
+        .qualifier(q)
+        .organization(o)
+        .application(a)
+        .build()
+    {
+        Err(project_dirs::Error::InvalidProjectId { component, .. }) => component,
+        other => panic!("Expected invalid id, got {other:?}"),
+    };
+
+    assert_eq!(invalid("", O, A), "qualifier");
+    assert_eq!(invalid("com..example", O, A), "qualifier");
+    assert_eq!(invalid("my com", O, A), "qualifier");
+    assert_eq!(invalid(Q, " ", A), "organization");
+    assert_eq!(invalid(Q, O, "żółć"), "application");
+
+    // Naming mode is used for validation, identifiers and the project
+    let id = ProjectId::builder()
+        .qualifier(Q)
+        .organization(O)
+        .application("żółć")
+        .naming_mode(project_dirs::NamingMode::Unicode)
+        .build()
+        .unwrap();
+    assert_eq!(id.dbus_name(), "com.my_org_corp.zolc");
+    assert_eq!(id.windows_path(), "my-org Corp\\żółć");
+    let project = id.project();
+    assert_eq!(project.naming_mode(), project_dirs::NamingMode::Unicode);
+    assert_eq!(project.application_name_unix(), "żółć");
+
+    // Deserialization is validated by the builder
+    let json = serde_json::to_value(&id).unwrap();
+    assert_eq!(serde_json::from_value::<ProjectId>(json).unwrap(), id);
+    let error = serde_json::from_value::<ProjectId>(serde_json::json!({
+        "qualifier": "my com",
+        "organization": O,
+        "application": A
+    }))
+    .unwrap_err();
+    assert!(error.to_string().contains("qualifier"), "{error}");
+    assert!(
+        serde_json::from_value::<ProjectId>(serde_json::json!({
+            "qualifier": Q,
+            "organization": "",
+            "application": A
+        }))
+        .is_err()
+    );
+    assert_eq!(invalid(Q, O, &"a".repeat(300)), "application");
+    assert!(
+        ProjectId::builder()
+            .qualifier(Q)
+            .application(A)
+            .build()
+            .is_err()
+    );
+}
+
+#[test]
+fn sandbox_dirs() {
+    use project_dirs::Error;
+    use project_dirs::env::MemoryEnv;
+    use project_dirs::strategy::sandbox::{Sandbox, SandboxEnv, SandboxKind};
+
+    let project = Project::new(Q, O, A);
+    let flatpak = MemoryEnv::new()
+        .with_home_dir("/home/me")
+        .with_var("FLATPAK_ID", "com.example.App")
+        .with_var("XDG_RUNTIME_DIR", "/run/user/1000")
+        // Flatpak takes precedence
+        .with_var("SNAP_USER_DATA", "/home/me/snap/app/1");
+
+    let env = SandboxEnv::from_source(&flatpak);
+    assert_eq!(
+        env.sandbox(),
+        Some(SandboxKind::Flatpak("com.example.App".to_string()))
+    );
+    let dirs = project.sandbox_user_with_env(env).unwrap();
+    assert_eq!(
+        dirs.config,
+        PathBuf::from("/home/me/.var/app/com.example.App/config/funny-bunny-v-2137-0")
+    );
+    assert_eq!(
+        dirs.log,
+        PathBuf::from("/home/me/.var/app/com.example.App/.local/state/funny-bunny-v-2137-0/log")
+    );
+    assert_eq!(
+        dirs.runtime,
+        Some(PathBuf::from(
+            "/run/user/1000/app/com.example.App/funny-bunny-v-2137-0"
+        ))
+    );
+    assert!(matches!(
+        project.sandbox_system_with_env(SandboxEnv::from_source(&flatpak)),
+        Err(Error::UnsupportedPlatform(_))
+    ));
+
+    let snap = MemoryEnv::new()
+        .with_home_dir("/home/me")
+        .with_var("SNAP_NAME", "app")
+        .with_var("SNAP_USER_DATA", "/home/me/snap/app/12")
+        .with_var("SNAP_USER_COMMON", "/home/me/snap/app/common")
+        .with_var("SNAP_DATA", "/var/snap/app/12")
+        .with_var("SNAP_COMMON", "/var/snap/app/common");
+
+    let dirs = project
+        .sandbox_user_with_env(SandboxEnv::from_source(&snap))
+        .unwrap();
+    assert_eq!(
+        dirs.data,
+        PathBuf::from("/home/me/snap/app/12/.local/share/funny-bunny-v-2137-0")
+    );
+    assert_eq!(
+        dirs.cache,
+        PathBuf::from("/home/me/snap/app/common/.cache/funny-bunny-v-2137-0")
+    );
+    assert_eq!(dirs.runtime, None);
+
+    let dirs = project
+        .sandbox_system_with_env(SandboxEnv::from_source(&snap))
+        .unwrap();
+    assert_eq!(
+        dirs.data,
+        PathBuf::from("/var/snap/app/12/funny-bunny-v-2137-0/data")
+    );
+    assert_eq!(
+        dirs.cache,
+        PathBuf::from("/var/snap/app/common/funny-bunny-v-2137-0/cache")
+    );
+
+    let no_sandbox = SandboxEnv::from_source(&MemoryEnv::new().with_home_dir("/home/me"));
+    assert_eq!(no_sandbox.sandbox(), None);
+    assert!(matches!(
+        project.sandbox_user_with_env(no_sandbox),
+        Err(Error::SandboxNotDetected)
+    ));
+
+    #[cfg(all(target_family = "unix", not(target_os = "macos")))]
+    {
+        let dirs = project.project_dirs_with_env(&flatpak);
+        assert_eq!(
+            dirs.user,
+            project
+                .sandbox_user_with_env(SandboxEnv::from_source(&flatpak))
+                .unwrap()
+                .into()
+        );
+
+        // Snap service: no user data, user scope falls back to XDG
+        let service = MemoryEnv::new()
+            .with_home_dir("/home/me")
+            .with_var("SNAP_NAME", "app")
+            .with_var("SNAP_DATA", "/var/snap/app/12");
+        assert!(matches!(
+            SandboxEnv::from_source(&service).sandbox(),
+            Some(SandboxKind::Snap(_))
+        ));
+        let dirs = project.project_dirs_with_env(&service);
+        assert_eq!(
+            dirs.user.get(&Directory::Config),
+            Some(&PathBuf::from("/home/me/.config/funny-bunny-v-2137-0"))
+        );
+        assert_eq!(
+            dirs.user.get(&Directory::Data),
+            Some(&PathBuf::from("/home/me/.local/share/funny-bunny-v-2137-0"))
+        );
+    }
+}
+
+#[test]
+fn systemd_dirs() {
+    use project_dirs::Error;
+    use project_dirs::env::MemoryEnv;
+    use project_dirs::strategy::fhs::Fhs as _;
+    use project_dirs::strategy::systemd::{Systemd, SystemdEnv};
+
+    let project = Project::new(Q, O, A);
+    let fhs = project.fhs();
+
+    // Nothing passed by systemd
+    let env = SystemdEnv::from_source(&MemoryEnv::new());
+    assert!(env.is_empty());
+    assert_eq!(project.systemd_with_env(env).unwrap(), fhs);
+
+    let env = MemoryEnv::new()
+        .with_var(
+            "STATE_DIRECTORY",
+            "/var/lib/private/other:/var/lib/private/funny-bunny-v-2137-0",
+        )
+        .with_var("CACHE_DIRECTORY", "/var/cache/private/cache-a::")
+        .with_var("RUNTIME_DIRECTORY", "/run/funny-bunny-v-2137-0");
+
+    let dirs = project
+        .systemd_with_env(SystemdEnv::from_source(&env))
+        .unwrap();
+    assert_eq!(
+        dirs.state,
+        PathBuf::from("/var/lib/private/funny-bunny-v-2137-0")
+    );
+    assert_eq!(dirs.data, dirs.state);
+    assert_eq!(dirs.cache, PathBuf::from("/var/cache/private/cache-a"));
+    assert_eq!(
+        dirs.runtime,
+        Some(PathBuf::from("/run/funny-bunny-v-2137-0"))
+    );
+    assert_eq!(dirs.log, fhs.log);
+    assert_eq!(dirs.config, fhs.config);
+    assert_eq!(dirs.bin, fhs.bin);
+
+    let mut env = SystemdEnv::default();
+    env.extend_with_env(
+        [("LOGS_DIRECTORY", Some("log/relative"))].into_iter(),
+        false,
+    );
+    assert!(matches!(
+        project.systemd_with_env(env),
+        Err(Error::InvalidEnvValue { name, .. }) if name == "LOGS_DIRECTORY"
+    ));
+}
+
+#[test]
+fn systemd_service_directives() {
+    use project_dirs::dir_utils::ProvisionPolicy;
+    use project_dirs::env::MemoryEnv;
+    use project_dirs::strategy::systemd::{ServiceDirectives, UnitDirectory};
+
+    let project = Project::new(Q, O, A);
+    let policy = ProvisionPolicy::default().with_mode(Directory::State, 0o750);
+    // Env overrides of the generating process do not leak into the unit
+    let overridden = project
+        .clone()
+        .with_env_overrides(project_dirs::EnvOverrides::with_prefix("PD_SYSTEMD_UNIT"));
+    let env = MemoryEnv::new().with_var("PD_SYSTEMD_UNIT_STATE_DIR", "/home/me/state");
+    assert_eq!(
+        overridden
+            .project_dirs_with_env(&env)
+            .system
+            .get(&Directory::State),
+        Some(&PathBuf::from("/home/me/state"))
+    );
+    let directives = ServiceDirectives::for_project(&overridden, &policy);
+    assert_eq!(
+        directives,
+        ServiceDirectives::for_project(&project, &policy)
+    );
+
+    let name = PathBuf::from("funny-bunny-v-2137-0");
+    for unit in [
+        UnitDirectory::Runtime,
+        UnitDirectory::State,
+        UnitDirectory::Cache,
+        UnitDirectory::Logs,
+        UnitDirectory::Configuration,
+    ] {
+        assert_eq!(directives.directories.get(&unit), Some(&vec![name.clone()]));
+    }
+    assert_eq!(directives.modes.get(&UnitDirectory::State), Some(&0o750));
+    assert_eq!(directives.modes.get(&UnitDirectory::Runtime), Some(&0o700));
+
+    let rendered = directives.to_string();
+    assert!(rendered.starts_with("[Service]\n"));
+    assert!(rendered.contains("StateDirectory=funny-bunny-v-2137-0\nStateDirectoryMode=0750\n"));
+    assert!(rendered.contains("ConfigurationDirectory=funny-bunny-v-2137-0\n"));
+    assert!(rendered.ends_with(
+        "ReadWritePaths=/run/funny-bunny-v-2137-0 /var/cache/funny-bunny-v-2137-0 \
+         /var/lib/funny-bunny-v-2137-0 /var/log/funny-bunny-v-2137-0\n"
+    ));
+
+    // Directories outside of the systemd bases are only writable
+    let dirs = ProjectDirs::new(HashMap::from([
+        (Directory::Data, PathBuf::from("/var/lib/private/app")),
+        (
+            Directory::State,
+            PathBuf::from("/var/lib/private/app/state"),
+        ),
+        (Directory::Cache, PathBuf::from("/srv/app cache")),
+        (Directory::Bin, PathBuf::from("/usr/bin")),
+    ]));
+    let directives = ServiceDirectives::new(&dirs, &policy);
+    assert_eq!(
+        directives.directories.get(&UnitDirectory::State),
+        Some(&vec![PathBuf::from("app"), PathBuf::from("app/state")])
+    );
+    assert_eq!(directives.directories.get(&UnitDirectory::Cache), None);
+    assert!(directives.to_string().ends_with(
+        "ReadWritePaths=\"/srv/app cache\" /var/lib/private/app /var/lib/private/app/state\n"
+    ));
+}
+
+#[test]
+fn systemd_tmpfiles_sysusers() {
+    use project_dirs::strategy::fhs::Fhs as _;
+    use project_dirs::strategy::systemd::{OwnershipPolicy, sysusers_entries, tmpfiles_entries};
+
+    let project = Project::new(Q, O, A);
+    let policy = OwnershipPolicy::new("bunny").with_cache_age("30d");
+    let lines: Vec<String> = tmpfiles_entries(&project.fhs().into(), &policy)
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+
+    assert_eq!(
+        lines,
+        vec![
+            "d /run/funny-bunny-v-2137-0 0700 bunny bunny -",
+            "d /var/cache/funny-bunny-v-2137-0 0755 bunny bunny 30d",
+            "d /var/lib/funny-bunny-v-2137-0 0755 bunny bunny -",
+            "d /var/log/funny-bunny-v-2137-0 0755 bunny bunny -",
+        ]
+    );
+    assert_eq!(
+        sysusers_entries(&policy, "Funny bunny"),
+        vec!["u bunny - \"Funny bunny\" - -"]
+    );
+
+    let policy = policy.with_group("pets").with_runtime_age("1d");
+    let entries = tmpfiles_entries(&project.fhs().into(), &policy);
+    assert!(entries.iter().all(|e| e.group == "pets"));
+    assert_eq!(entries[0].age.as_deref(), Some("1d"));
+    assert_eq!(
+        sysusers_entries(&policy, "Funny bunny"),
+        vec!["g pets -", "u bunny - \"Funny bunny\" - -", "m bunny pets"]
+    );
+}
+
+#[test]
+fn named_instances() {
+    use project_dirs::INSTANCE_DIRECTORIES;
+    use project_dirs::dir_utils::ProvisionPolicy;
+    use project_dirs::env::MemoryEnv;
+    use project_dirs::strategy::fhs::Fhs as _;
+    use project_dirs::strategy::systemd::{ServiceDirectives, Systemd as _, SystemdEnv};
+
+    let project = Project::new(Q, O, A).with_instance("Blue Sky");
+    assert_eq!(project.instance(), Some("blue-sky"));
+    assert_eq!(Project::new(Q, O, A).with_instance("?!").instance(), None);
+
+    let base: ProjectDirs = project.fhs().into();
+    let dirs = project.finalize_project_dirs(base.clone());
+    for (dir, path) in &dirs.0 {
+        if INSTANCE_DIRECTORIES.contains(dir) {
+            assert_eq!(path, &base.get(dir).unwrap().join("blue-sky"));
+        } else {
+            assert_eq!(path, base.get(dir).unwrap());
+        }
+    }
+
+    // Each instance has own version
+    let versioned = Project::new(Q, O, A)
+        .with_instance("blue")
+        .with_version("2");
+    assert_eq!(
+        versioned
+            .finalize_project_dirs(base.clone())
+            .get(&Directory::Data),
+        Some(&PathBuf::from("/var/lib/funny-bunny-v-2137-0/blue/v2"))
+    );
+
+    // Template unit uses the `%i` specifier
+    let directives = ServiceDirectives::for_project(&project, &ProvisionPolicy::default());
+    let rendered = directives.to_string();
+    assert!(rendered.contains("StateDirectory=funny-bunny-v-2137-0/%i\n"));
+    assert!(rendered.contains("CacheDirectory=funny-bunny-v-2137-0\n"));
+
+    // Instance passed by systemd is not duplicated
+    let env = MemoryEnv::new()
+        .with_var(
+            "STATE_DIRECTORY",
+            "/var/lib/private/funny-bunny-v-2137-0/blue-sky",
+        )
+        .with_var("CACHE_DIRECTORY", "/var/cache/blue-sky");
+    let systemd = project
+        .systemd_with_env(SystemdEnv::from_source(&env))
+        .unwrap();
+    let dirs = project.finalize_project_dirs(systemd.into());
+    assert_eq!(
+        dirs.get(&Directory::State),
+        Some(&PathBuf::from(
+            "/var/lib/private/funny-bunny-v-2137-0/blue-sky"
+        ))
+    );
+    assert_eq!(
+        dirs.get(&Directory::Cache),
+        Some(&PathBuf::from("/var/cache/blue-sky"))
+    );
+}
+
+#[test]
+fn portable_mode() {
+    use project_dirs::env::MemoryEnv;
+    use project_dirs::strategy::portable::{PORTABLE_MARKER, Portable as _};
+    use project_dirs::strategy::unix::Unix as _;
+
+    let root = ScratchDir::new("portable");
+    std::fs::create_dir_all(&root).unwrap();
+
+    let project = Project::new(Q, O, A);
+    let env = MemoryEnv::new()
+        .with_home_dir("/home/me")
+        .with_current_dir("/work")
+        .with_current_exe(root.join("tool"));
+
+    // Directory of the binary, not the binary itself
+    assert_eq!(
+        project.unix_binary_with_env(&env).unwrap().config,
+        root.join(".funny-bunny-v-2137-0")
+    );
+
+    let portable = project.portable_with_env(&env).unwrap();
+    assert_eq!(portable.bin, *root);
+    assert_eq!(portable.data, root.join("funny-bunny-v-2137-0-data/data"));
+
+    // Not enabled yet
+    assert_eq!(project.portable_exe_dir_with_env(&env), None);
+    assert_ne!(
+        project.project_dirs_with_env(&env).user,
+        portable.clone().into()
+    );
+
+    std::fs::write(root.join(PORTABLE_MARKER), "").unwrap();
+    assert_eq!(
+        project.portable_exe_dir_with_env(&env),
+        Some(root.to_path_buf())
+    );
+    assert_eq!(
+        project.project_dirs_with_env(&env).user,
+        portable.clone().into()
+    );
+
+    // Data directory enables it as well
+    std::fs::remove_file(root.join(PORTABLE_MARKER)).unwrap();
+    std::fs::create_dir(root.join("funny-bunny-v-2137-0-data")).unwrap();
+    assert_eq!(
+        project.portable_exe_dir_with_env(&env),
+        Some(root.to_path_buf())
+    );
+}
+
+#[test]
+fn env_overrides() {
+    use project_dirs::env::MemoryEnv;
+    use project_dirs::strategy::fhs::Fhs as _;
+    use project_dirs::{CustomDirectory, EnvOverrides};
+
+    let project = Project::new(Q, O, A).with_custom_directory(
+        CustomDirectory::new("plugins", Directory::Data, "plugins").unwrap(),
+    );
+    let overrides = EnvOverrides::new();
+    assert_eq!(
+        overrides.variable_name(&project, &Directory::Data),
+        "FUNNY_BUNNY_V_2137_0_DATA_DIR"
+    );
+    assert_eq!(
+        EnvOverrides::with_prefix("BUNNY").variable_name(&project, &Directory::ProjectRoot),
+        "BUNNY_PROJECT_ROOT_DIR"
+    );
+
+    let env = MemoryEnv::new()
+        .with_var("FUNNY_BUNNY_V_2137_0_DATA_DIR", "/srv/big-disk")
+        .with_var("FUNNY_BUNNY_V_2137_0_CACHE_DIR", "relative")
+        .with_var("FUNNY_BUNNY_V_2137_0_PLUGINS_DIR", "/opt/plugins");
+
+    // Disabled by default
+    let dirs = project.finalize_project_dirs_with_env(project.fhs().into(), &env);
+    assert_eq!(
+        dirs.get(&Directory::Data),
+        Some(&PathBuf::from("/var/lib/funny-bunny-v-2137-0"))
+    );
+
+    let project = project.with_env_overrides(overrides);
+    let dirs = project.finalize_project_dirs_with_env(project.fhs().into(), &env);
+    assert_eq!(
+        dirs.get(&Directory::Data),
+        Some(&PathBuf::from("/srv/big-disk"))
+    );
+    assert_eq!(
+        dirs.get(&Directory::Cache),
+        Some(&PathBuf::from("/var/cache/funny-bunny-v-2137-0"))
+    );
+    assert_eq!(
+        dirs.get(&Directory::Custom("plugins".to_string())),
+        Some(&PathBuf::from("/opt/plugins"))
+    );
+}
+
+#[test]
+fn doctor_dirs() {
+    use project_dirs::doctor::{Doctor as _, EntryKind, Issue, Severity};
+
+    let root = ScratchDir::new("doctor");
+
+    let dirs = project_dirs::strategy::unix::unix(&root.join("app"));
+    std::fs::create_dir_all(&dirs.data).unwrap();
+    std::fs::write(&dirs.log, "").unwrap();
+
+    let report = dirs.diagnose();
+    let data = &report.directories[&Directory::Data];
+    assert_eq!(data.kind, Some(EntryKind::Directory));
+    assert!(data.issues.is_empty());
+    assert!(data.free_space.is_some() || cfg!(not(unix)));
+
+    let log = &report.directories[&Directory::Log];
+    assert_eq!(log.kind, Some(EntryKind::File));
+    assert_eq!(log.issues, vec![Issue::NotADirectory]);
+
+    let cache = &report.directories[&Directory::Cache];
+    assert_eq!(cache.kind, None);
+    assert_eq!(cache.issues, vec![Issue::Missing]);
+    assert_eq!(Issue::Missing.severity(), Severity::Warning);
+    // Checked on the existing parent
+    assert_eq!(
+        cache.filesystem,
+        report.directories[&Directory::Data].filesystem
+    );
+    assert!(report.has_problems());
+
+    #[cfg(unix)]
+    {
+        use project_dirs::env::{EnvSource as _, MemoryEnv, SystemEnv};
+        use std::os::unix::fs::PermissionsExt;
+
+        std::fs::set_permissions(&dirs.data, std::fs::Permissions::from_mode(0o777)).unwrap();
+        std::fs::remove_file(&dirs.log).unwrap();
+        std::os::unix::fs::symlink(root.join("missing"), &dirs.log).unwrap();
+
+        let uid = SystemEnv.uid().unwrap();
+        let report = dirs.diagnose_with_env(&MemoryEnv::new().with_uid(uid + 1));
+
+        let data = &report.directories[&Directory::Data];
+        assert_eq!(data.mode, Some(0o777));
+        assert_eq!(data.owner, Some(uid));
+        assert_eq!(
+            data.issues,
+            vec![
+                Issue::ForeignOwner {
+                    owner: uid,
+                    uid: uid + 1
+                },
+                Issue::WorldWritable
+            ]
+        );
+
+        let log = &report.directories[&Directory::Log];
+        assert_eq!(log.symlink_target, Some(root.join("missing")));
+        assert_eq!(
+            log.issues,
+            vec![Issue::BrokenSymlink {
+                target: root.join("missing")
+            }]
+        );
+    }
+
+    // Entries that failed to resolve are reported as well
+    let builder: project_dirs_builder::Builder = serde_json::from_value(serde_json::json!({
+        "qualifier": Q,
+        "organization": O,
+        "application": A,
+        "spec": {
+            "unknown": {"strategy": "registered", "strategy_config": "unknown"}
+        }
+    }))
+    .unwrap();
+    let result = builder.doctor(None).unwrap();
+    assert!(result.reports.is_empty());
+    assert_eq!(result.errors["unknown"], "Unknown strategy: unknown");
+    assert!(result.has_problems());
+    assert!(builder.doctor(Some("unknown")).is_err());
+}
+
+#[test]
+fn instance_lock() {
+    use project_dirs::Error;
+    use project_dirs::lock::{LOCK_FILE, locked_pid};
+
+    let root = ScratchDir::new("lock");
+
+    let mut dirs = project_dirs::strategy::unix::unix(&root.join("app"));
+    dirs.runtime = None;
+    let dirs = ProjectDirs::from(dirs);
+
+    // State is used without the runtime
+    assert_eq!(dirs.lock_dir(), dirs.get(&Directory::State));
+    let path = dirs.lock_dir().unwrap().join(LOCK_FILE);
+
+    let lock = dirs.instance_lock().unwrap();
+    assert_eq!(lock.path(), path);
+    assert_eq!(lock.stale_pid(), None);
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap().trim(),
+        std::process::id().to_string()
+    );
+    assert_eq!(locked_pid(&path), Some(std::process::id()));
+
+    match dirs.instance_lock() {
+        Err(Error::Locked { path: locked, pid }) => {
+            assert_eq!(locked, path);
+            assert_eq!(pid, Some(std::process::id()));
+        }
+        other => panic!("Expected locked error, got {other:?}"),
+    }
+
+    drop(lock);
+    assert!(!path.exists());
+
+    #[cfg(unix)]
+    {
+        // Left by the crashed process
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let dead_pid = child.id();
+        child.wait().unwrap();
+        std::fs::write(&path, format!("{dead_pid}\n")).unwrap();
+        assert_eq!(locked_pid(&path), None);
+
+        let lock = dirs.instance_lock().unwrap();
+        assert_eq!(lock.stale_pid(), Some(dead_pid));
+    }
+
+    assert!(ProjectDirs::empty().instance_lock().is_err());
+}
+
+#[test]
+fn socket_paths() {
+    use project_dirs::Error;
+    use project_dirs::dir_utils::ProvisionPolicy;
+    use project_dirs::env::MemoryEnv;
+    use project_dirs::socket::SUN_PATH_MAX;
+
+    let env = MemoryEnv::new().with_uid(4242);
+    let dirs = ProjectDirs::new(HashMap::from([(
+        Directory::Runtime,
+        PathBuf::from("/run/user/4242/app"),
+    )]));
+    let socket = dirs.socket_path_with_env("api.sock", &env).unwrap();
+    assert_eq!(socket.path, PathBuf::from("/run/user/4242/app/api.sock"));
+    assert!(!socket.is_fallback());
+
+    let root = ScratchDir::new("socket");
+    let deep = root.join("x".repeat(SUN_PATH_MAX)).join("app");
+    let dirs = ProjectDirs::new(HashMap::from([(Directory::State, deep.clone())]));
+
+    let socket = dirs.socket_path_with_env("api.sock", &env).unwrap();
+    assert!(socket.is_fallback());
+    assert_eq!(socket.preferred, deep.join("api.sock"));
+    assert_eq!(socket.directory, Directory::State);
+    assert!(socket.path.starts_with("/tmp"));
+    assert!(socket.path.as_os_str().len() < SUN_PATH_MAX);
+    // Deterministic, so clients find the server
+    assert_eq!(dirs.socket_path_with_env("api.sock", &env).unwrap(), socket);
+    assert_eq!(
+        dirs.socket_path_with_env("other.sock", &env)
+            .unwrap()
+            .path
+            .parent(),
+        socket.path.parent()
+    );
+
+    assert!(matches!(
+        dirs.socket_path_with_env(&"x".repeat(SUN_PATH_MAX), &env),
+        Err(Error::SocketPathTooLong { .. })
+    ));
+    assert!(ProjectDirs::empty().socket_path("api.sock").is_err());
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let socket = dirs.socket_path("api.sock").unwrap();
+        let fallback_dir = socket.path.parent().unwrap();
+        let _ = std::fs::remove_dir_all(fallback_dir);
+
+        socket.prepare(&ProvisionPolicy::default()).unwrap();
+        let mode = std::fs::metadata(fallback_dir)
+            .unwrap()
+            .permissions()
+            .mode()
+            & 0o777;
+        // Private, regardless of the preferred directory mode
+        assert_eq!(mode, 0o700);
+        std::os::unix::net::UnixListener::bind(&socket.path).unwrap();
+        std::fs::remove_dir_all(fallback_dir).unwrap();
+
+        // Planted by someone else as a symlink to the directory of the victim
+        let victim = root.join("victim");
+        std::fs::create_dir_all(&victim).unwrap();
+        std::fs::set_permissions(&victim, std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::os::unix::fs::symlink(&victim, fallback_dir).unwrap();
+        assert!(matches!(
+            socket.prepare(&ProvisionPolicy::default()),
+            Err(Error::UnsafeDirectory { .. })
+        ));
+        let mode = std::fs::metadata(&victim).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, 0o755);
+        std::fs::remove_file(fallback_dir).unwrap();
+
+        // Existing directory accessible by others
+        std::fs::create_dir(fallback_dir).unwrap();
+        std::fs::set_permissions(fallback_dir, std::fs::Permissions::from_mode(0o777)).unwrap();
+        assert!(matches!(
+            socket.prepare(&ProvisionPolicy::default()),
+            Err(Error::UnsafeDirectory { .. })
+        ));
+
+        std::fs::remove_dir_all(fallback_dir).unwrap();
+    }
+}
+
+#[test]
+fn temp_dirs() {
+    use project_dirs::dir_utils::ProvisionPolicy;
+    use project_dirs::temp::TEMP_AREA;
+    use std::time::Duration;
+
+    let root = ScratchDir::new("temp");
+
+    let mut dirs = project_dirs::strategy::unix::unix(&root.join("app"));
+    dirs.runtime = None;
+    let dirs = ProjectDirs::from(dirs);
+    // Cache is used without the runtime
+    assert_eq!(
+        dirs.temp_area(),
+        Some(dirs.get(&Directory::Cache).unwrap().join(TEMP_AREA))
+    );
+
+    let policy = ProvisionPolicy::default();
+    let first = dirs.temp_dir(&policy).unwrap();
+    let second = dirs.temp_dir(&policy).unwrap();
+    assert_ne!(first.path(), second.path());
+    assert!(first.path().is_dir());
+    assert!(first.path().starts_with(dirs.temp_area().unwrap()));
+
+    std::fs::write(first.path().join("scratch"), "").unwrap();
+    let first_path = first.path().to_path_buf();
+    drop(first);
+    assert!(!first_path.exists());
+
+    let kept = second.keep();
+    assert!(kept.is_dir());
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mode = std::fs::metadata(&kept).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode, policy.mode(&Directory::Cache));
+
+        // Left by the crashed process
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let dead_pid = child.id();
+        child.wait().unwrap();
+        let stale = dirs.temp_area().unwrap().join(format!("{dead_pid}-0-0"));
+        std::fs::create_dir(&stale).unwrap();
+
+        assert!(
+            dirs.sweep_stale(Duration::from_secs(3600))
+                .unwrap()
+                .is_empty()
+        );
+
+        // Writes inside do not change modification time of the directory
+        let hour_ago = std::time::SystemTime::now() - Duration::from_secs(3600);
+        let log = stale.join("nested").join("log");
+        std::fs::create_dir(stale.join("nested")).unwrap();
+        std::fs::write(&log, "").unwrap();
+        for dir in [stale.join("nested"), stale.clone()] {
+            std::fs::File::open(dir)
+                .unwrap()
+                .set_modified(hour_ago)
+                .unwrap();
+        }
+        assert!(
+            dirs.sweep_stale(Duration::from_secs(1800))
+                .unwrap()
+                .is_empty()
+        );
+
+        std::fs::File::options()
+            .write(true)
+            .open(&log)
+            .unwrap()
+            .set_modified(hour_ago)
+            .unwrap();
+        assert_eq!(
+            dirs.sweep_stale(Duration::from_secs(1800)).unwrap(),
+            vec![stale.clone()]
+        );
+        assert!(!stale.exists());
+
+        // Symlinks inside are not followed
+        std::fs::create_dir(&stale).unwrap();
+        std::os::unix::fs::symlink(root.join("missing"), stale.join("link")).unwrap();
+        assert_eq!(
+            dirs.sweep_stale(Duration::ZERO).unwrap(),
+            vec![stale.clone()]
+        );
+    }
+
+    // Directories of the current process are never swept
+    assert!(kept.is_dir());
+    assert!(
+        ProjectDirs::empty()
+            .sweep_stale(Duration::ZERO)
+            .unwrap()
+            .is_empty()
+    );
+    assert!(ProjectDirs::empty().temp_dir(&policy).is_err());
+}
@@ -2,7 +2,8 @@ use std::{
     env,
     fs::read_dir,
     io::{self, ErrorKind},
-    path::PathBuf,
+    ops::Deref,
+    path::{Path, PathBuf},
 };
 
 pub const Q: &str = "com";
@@ -24,3 +25,35 @@ pub fn get_project_root() -> io::Result<PathBuf> {
         "Ran out of places to find Cargo.toml",
     ))
 }
+
+/// `project-dirs-<name>-<pid>` path in the system temp dir. Leftovers of the previous runs are
+/// removed, the directory itself is not created. Removed on drop, also when the test fails
+pub struct ScratchDir(PathBuf);
+
+impl ScratchDir {
+    pub fn new(name: &str) -> Self {
+        let path = env::temp_dir().join(format!("project-dirs-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        Self(path)
+    }
+}
+
+impl Deref for ScratchDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for ScratchDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for ScratchDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}